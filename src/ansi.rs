@@ -0,0 +1,83 @@
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Remove ANSI/VT escape sequences (CSI, OSC and simple two-byte escapes) from captured bytes.
+/// Plain text, including non-ASCII UTF-8, is passed through unchanged.
+pub fn strip(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == ESC {
+            i += escape_len(&input[i..]);
+        } else {
+            out.push(input[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Length of the escape sequence at the start of `input` (which begins with ESC).
+pub fn escape_len(input: &[u8]) -> usize {
+    // Lone ESC at the very end: drop it.
+    let Some(&next) = input.get(1) else {
+        return 1;
+    };
+    match next {
+        // CSI: ESC [ <params 0x30-0x3F>* <intermediates 0x20-0x2F>* <final 0x40-0x7E>
+        b'[' => {
+            let mut j = 2;
+            while j < input.len() && (0x30..=0x3f).contains(&input[j]) {
+                j += 1;
+            }
+            while j < input.len() && (0x20..=0x2f).contains(&input[j]) {
+                j += 1;
+            }
+            if j < input.len() && (0x40..=0x7e).contains(&input[j]) {
+                j + 1
+            } else {
+                // Malformed sequence: drop the introducer, keep the rest as text.
+                j
+            }
+        }
+        // OSC/DCS/APC/PM/SOS: terminated by BEL or ST (ESC \)
+        b']' | b'P' | b'_' | b'^' | b'X' => {
+            let mut j = 2;
+            loop {
+                if j >= input.len() {
+                    return j;
+                }
+                if input[j] == BEL {
+                    return j + 1;
+                }
+                if input[j] == ESC && input.get(j + 1) == Some(&b'\\') {
+                    return j + 2;
+                }
+                j += 1;
+            }
+        }
+        // nF escapes, e.g. ESC ( B (character set selection)
+        0x20..=0x2f => {
+            let mut j = 1;
+            while j < input.len() && (0x20..=0x2f).contains(&input[j]) {
+                j += 1;
+            }
+            (j + 1).min(input.len())
+        }
+        // Fp/Fe/Fs escapes, e.g. ESC 7, ESC M
+        0x30..=0x7e => 2,
+        _ => 1,
+    }
+}
+
+/// Like [`strip`], but for already decoded text (e.g. parsed record blocks).
+pub fn strip_str(input: &str) -> String {
+    let stripped = strip(input.as_bytes());
+    String::from_utf8(stripped)
+        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// True if the text contains at least one escape character.
+pub fn has_escapes(input: &str) -> bool {
+    input.as_bytes().contains(&0x1b)
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use rommy::parser::RecordReader;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+
+use crate::progress::format_bytes;
+use crate::{CopyConfig, CopyFormat, color_is_enabled, history, markdown, rommy_note_cyan};
+
+/// Encoded size above which many terminals (xterm, hterm, ...) truncate or drop OSC 52.
+const OSC52_LIMIT: usize = 100_000;
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// OSC 52 "set clipboard" sequence. Inside tmux it is wrapped in a passthrough
+/// sequence so that it reaches the outer terminal.
+fn osc52(encoded: &str) -> String {
+    let seq = format!("\x1b]52;c;{encoded}\x07");
+    if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+    } else {
+        seq
+    }
+}
+
+/// Hand `text` to the terminal's clipboard: via stdout if it is a terminal
+/// (or `print` is set), otherwise via the controlling terminal.
+fn send(text: &str, print: bool) -> Result<()> {
+    let encoded = base64(text.as_bytes());
+    if encoded.len() > OSC52_LIMIT {
+        eprintln!(
+            "WARN: clipboard payload is {} after encoding; many terminals accept at most {} and may drop it",
+            format_bytes(encoded.len()),
+            format_bytes(OSC52_LIMIT)
+        );
+    }
+    let seq = osc52(&encoded);
+    if print || io::stdout().is_terminal() {
+        let mut out = io::stdout().lock();
+        out.write_all(seq.as_bytes())?;
+        out.flush()?;
+    } else {
+        let mut tty = OpenOptions::new()
+            .write(true)
+            .open("/dev/tty")
+            .context("No terminal to copy to (use --print to write the sequence to stdout)")?;
+        tty.write_all(seq.as_bytes())?;
+        tty.flush()?;
+    }
+    Ok(())
+}
+
+/// What gets copied: one record (or the whole file if `record` is None),
+/// rendered as Markdown or as the stored `.rommy` text.
+fn content(
+    path: &Path,
+    record: Option<usize>,
+    format: CopyFormat,
+    budget: Option<usize>,
+) -> Result<String> {
+    let display = path.display().to_string();
+    match format {
+        CopyFormat::Markdown => {
+            let records = rommy::parser::parse_file(path)
+                .with_context(|| format!("failed to parse {display}"))?;
+            anyhow::ensure!(!records.is_empty(), "No records found in {display}");
+            let selected: Vec<_> = match record {
+                Some(n) => {
+                    anyhow::ensure!(
+                        (1..=records.len()).contains(&n),
+                        "--record {} is out of range (1..={})",
+                        n,
+                        records.len()
+                    );
+                    vec![(n, &records[n - 1])]
+                }
+                None => records
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| (i + 1, r))
+                    .collect(),
+            };
+            Ok(markdown::render(&display, &selected, budget))
+        }
+        CopyFormat::Raw => {
+            let text = rommy::compression::read_to_string(path)
+                .with_context(|| format!("Cannot read {display}"))?;
+            let Some(n) = record else {
+                return Ok(text);
+            };
+            // Record starts as the parser sees them, so a marker-like line in
+            // captured output cannot shift the slice
+            let mut reader = RecordReader::new(text.as_bytes());
+            let mut offsets = Vec::new();
+            while let Some(parsed) = reader.next() {
+                parsed.with_context(|| format!("failed to parse {display}"))?;
+                if let Some(start) = reader.record_start() {
+                    offsets.push(start.offset as usize);
+                }
+            }
+            anyhow::ensure!(
+                (1..=offsets.len()).contains(&n),
+                "--record {} is out of range (1..={})",
+                n,
+                offsets.len()
+            );
+            let end = offsets.get(n).copied().unwrap_or(text.len());
+            Ok(text[offsets[n - 1]..end].to_string())
+        }
+    }
+}
+
+/// `rommy copy`: put a record on the clipboard via OSC 52.
+pub fn copy(cfg: CopyConfig) -> Result<()> {
+    anyhow::ensure!(
+        cfg.budget.is_none() || matches!(cfg.format, CopyFormat::Markdown),
+        "--budget requires --format markdown"
+    );
+    let colors = color_is_enabled(cfg.color);
+    let (path, record) = match cfg.path {
+        Some(path) => (path, cfg.record),
+        None => {
+            anyhow::ensure!(cfg.record.is_none(), "--record requires FILE");
+            let (path, record) = history::latest_record()?;
+            (path, Some(record))
+        }
+    };
+    let text = content(&path, record, cfg.format, cfg.budget)?;
+    send(&text, cfg.print)?;
+    let what = match record {
+        Some(n) => format!("record {n} of {}", path.display()),
+        None => path.display().to_string(),
+    };
+    rommy_note_cyan(
+        colors,
+        &format!("Copied {what} ({})", format_bytes(text.len())),
+    );
+    Ok(())
+}
+
+/// `run --copy`: copy the record just written to `path` (its last record) as Markdown.
+pub fn copy_last_record(path: &Path) -> Result<()> {
+    let records = rommy::parser::parse_file(path)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    let text = content(path, Some(records.len()), CopyFormat::Markdown, None)?;
+    send(&text, false)
+}
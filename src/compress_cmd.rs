@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use rommy::compression::{self, Compression};
+use rommy::writer::{self, RecordLock};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::progress::format_bytes;
+use crate::{CompressConfig, collect_rommy_files, color_is_enabled, index, rommy_note_cyan};
+
+/// Convert one plain file; returns (old size, new size, new path), or `None` if it was skipped.
+fn compress_file(path: &Path, codec: Compression) -> Result<Option<(u64, u64, PathBuf)>> {
+    if Compression::from_path(path) != Compression::None {
+        return Ok(None);
+    }
+    // Same lock as `run`, so no record is appended while the file is converted
+    let lock = RecordLock::acquire(path)?;
+
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if Compression::detect(&bytes) != Compression::None {
+        return Ok(None);
+    }
+    let text = std::str::from_utf8(&bytes)
+        .with_context(|| format!("{} is not valid UTF-8", path.display()))?;
+    rommy::parser::parse_str(text)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let encoded = codec.encode(&bytes)?;
+    anyhow::ensure!(
+        codec.decode(&encoded)? == bytes,
+        "round trip check failed for {}",
+        path.display()
+    );
+
+    let target = compression::with_suffix(path, codec);
+    // A `run --out` may be writing the compressed name at the same time
+    let target_lock = RecordLock::acquire(&target)?;
+    anyhow::ensure!(!target.exists(), "{} already exists", target.display());
+    writer::replace_file(&target, &encoded, false, &target_lock)?;
+
+    // The compressed copy is in place; only now drop the original
+    index::forget(&[path.to_path_buf()])?;
+    fs::remove_file(path).with_context(|| format!("Cannot remove {}", path.display()))?;
+    index::update_after_write(&target)?;
+    drop(target_lock);
+    lock.remove();
+
+    Ok(Some((bytes.len() as u64, encoded.len() as u64, target)))
+}
+
+/// `rommy compress PATH...`: convert plain .rommy files in place.
+pub fn compress(cfg: CompressConfig) -> Result<()> {
+    anyhow::ensure!(
+        cfg.codec != Compression::None,
+        "--codec none would not compress anything"
+    );
+    let colors = color_is_enabled(cfg.color);
+    let mut files = Vec::new();
+    for path in &cfg.paths {
+        collect_rommy_files(path, &mut files)?;
+    }
+    files.sort();
+    files.dedup();
+
+    let (mut converted, mut failed) = (0usize, 0usize);
+    let (mut before, mut after) = (0u64, 0u64);
+    for file in &files {
+        match compress_file(file, cfg.codec) {
+            Ok(Some((old_size, new_size, target))) => {
+                converted += 1;
+                before += old_size;
+                after += new_size;
+                println!(
+                    "{} -> {} ({} -> {})",
+                    file.display(),
+                    target.display(),
+                    format_bytes(old_size as usize),
+                    format_bytes(new_size as usize)
+                );
+            }
+            Ok(None) => {}
+            Err(err) => {
+                failed += 1;
+                eprintln!("ERR {}: {:#}", file.display(), err);
+            }
+        }
+    }
+    rommy_note_cyan(
+        colors,
+        &format!(
+            "Compressed {} file(s) with {}: {} -> {}",
+            converted,
+            cfg.codec,
+            format_bytes(before as usize),
+            format_bytes(after as usize)
+        ),
+    );
+    if failed > 0 {
+        anyhow::bail!("{failed} file(s) could not be compressed");
+    }
+    Ok(())
+}
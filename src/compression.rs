@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How a .rommy file is stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain text (`.rommy`)
+    None,
+    /// Zstandard frames (`.rommy.zst`)
+    Zstd,
+    /// Gzip members (`.rommy.gz`)
+    Gzip,
+}
+
+impl Compression {
+    /// File name suffix added after `.rommy`
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Zstd => ".zst",
+            Compression::Gzip => ".gz",
+        }
+    }
+
+    /// Compression implied by the file name (`.zst`, `.gz`).
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Compression::Zstd,
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Compression::Gzip,
+            _ => Compression::None,
+        }
+    }
+
+    /// Compression recognised from the leading magic bytes.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Compress `data` into one self-contained frame (zstd) or member (gzip).
+    /// Frames can be concatenated, which is how records are appended.
+    pub fn encode(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Zstd => zstd::encode_all(data, 0).context("zstd compression failed"),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish().context("gzip compression failed")
+            }
+        }
+    }
+
+    /// Decompress all concatenated frames/members of `data`.
+    pub fn decode(self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self {
+            Compression::None => out.extend_from_slice(data),
+            Compression::Zstd => {
+                zstd::stream::read::Decoder::new(data)?
+                    .read_to_end(&mut out)
+                    .context("zstd decompression failed")?;
+            }
+            Compression::Gzip => {
+                flate2::read::MultiGzDecoder::new(data)
+                    .read_to_end(&mut out)
+                    .context("gzip decompression failed")?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+        })
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" | "off" => Ok(Compression::None),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            other => Err(format!(
+                "unknown compression '{other}' (expected zstd, gzip or none)"
+            )),
+        }
+    }
+}
+
+/// Whether `path` names a record file: `.rommy`, `.rommy.zst` or `.rommy.gz`.
+pub fn is_rommy_path(path: &Path) -> bool {
+    let inner = match Compression::from_path(path) {
+        Compression::None => path.to_path_buf(),
+        _ => path.with_extension(""),
+    };
+    inner
+        .extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("rommy"))
+}
+
+/// `path` with the suffix for `compression` in place of any existing `.zst`/`.gz`.
+pub fn with_suffix(path: &Path, compression: Compression) -> PathBuf {
+    let plain = match Compression::from_path(path) {
+        Compression::None => path.to_path_buf(),
+        _ => path.with_extension(""),
+    };
+    let mut name = plain.into_os_string();
+    name.push(compression.suffix());
+    PathBuf::from(name)
+}
+
+/// Read a record file, decompressing it if needed (detected from its content).
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let text = Compression::detect(&bytes)
+        .decode(&bytes)
+        .with_context(|| format!("failed to decompress {}", path.display()))?;
+    String::from_utf8(text).with_context(|| format!("{} is not valid UTF-8", path.display()))
+}
+
+/// Open a record file for streaming, decompressing it on the fly if needed.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    let file =
+        fs::File::open(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let head = reader
+        .fill_buf()
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(match Compression::detect(head) {
+        Compression::None => Box::new(reader),
+        Compression::Zstd => Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .with_context(|| format!("failed to decompress {}", path.display()))?,
+        )),
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+    })
+}
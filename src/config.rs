@@ -0,0 +1,51 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::outpath;
+
+/// Optional settings file `<root>/rommy.conf`, in the same `key: value` form as META.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn config_path() -> PathBuf {
+    outpath::default_root_dir().join("rommy.conf")
+}
+
+/// Settings from `rommy.conf`; empty if the file does not exist.
+pub fn load() -> Result<HashMap<String, String>> {
+    let path = config_path();
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    let mut settings = HashMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            anyhow::bail!(
+                "{}:{}: expected `key: value`, got {:?}",
+                path.display(),
+                n + 1,
+                line
+            );
+        };
+        settings.insert(key.trim().to_string(), value.trim().to_string());
+    }
+    Ok(settings)
+}
+
+/// Interpret a setting as a boolean (`true/false`, `yes/no`, `on/off`, `1/0`).
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!(
+            "invalid boolean '{value}' (expected true or false)"
+        )),
+    }
+}
@@ -0,0 +1,470 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{AttachConfig, RunConfig, StatusConfig, StatusFormat, StopConfig, StopSignal};
+use crate::{color_is_enabled, outpath, rommy_note_cyan, shell_join};
+
+const POLL: Duration = Duration::from_millis(200);
+
+/// Registry of detached runs: `<root>/runs/<id>.json` plus the live log `<id>.log`.
+fn runs_dir() -> PathBuf {
+    outpath::default_root_dir().join("runs")
+}
+
+fn entry_path(id: &str) -> PathBuf {
+    runs_dir().join(format!("{id}.json"))
+}
+
+fn log_path(id: &str) -> PathBuf {
+    runs_dir().join(format!("{id}.log"))
+}
+
+struct RunEntry {
+    id: String,
+    pid: u32,
+    /// `None` until the command has been spawned
+    child_pid: Option<u32>,
+    output_path: PathBuf,
+    start: DateTime<Utc>,
+    command: String,
+}
+
+impl RunEntry {
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "pid": self.pid,
+            "child_pid": self.child_pid,
+            "output_path": self.output_path.display().to_string(),
+            "log_path": log_path(&self.id).display().to_string(),
+            "start_ts": self.start.to_rfc3339(),
+            "command": self.command,
+        })
+    }
+
+    fn from_json(v: &Value) -> Option<Self> {
+        Some(RunEntry {
+            id: v["id"].as_str()?.to_string(),
+            pid: v["pid"].as_u64()? as u32,
+            child_pid: v["child_pid"].as_u64().map(|pid| pid as u32),
+            output_path: PathBuf::from(v["output_path"].as_str()?),
+            start: DateTime::parse_from_rfc3339(v["start_ts"].as_str()?)
+                .ok()?
+                .with_timezone(&Utc),
+            command: v["command"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        let value: Value = serde_json::from_str(&text).ok()?;
+        Self::from_json(&value)
+    }
+
+    /// Write via rename so readers never see a half-written entry.
+    fn save(&self) -> Result<()> {
+        let tmp = runs_dir().join(format!(".{}.json.tmp", self.id));
+        fs::write(&tmp, serde_json::to_string_pretty(&self.to_json())?)
+            .with_context(|| format!("Cannot write {}", tmp.display()))?;
+        fs::rename(&tmp, entry_path(&self.id))
+            .with_context(|| format!("Cannot register run {}", self.id))
+    }
+}
+
+/// Held by the supervisor while the child runs. Dropping it unregisters the run
+/// and deletes its live log, which tells `attach` and `stop` that the record has
+/// been finalised.
+pub struct Registration {
+    entry: RunEntry,
+}
+
+impl Registration {
+    /// Register the run before its command is spawned, so it can always be
+    /// found, and open the live log that captured output is teed into.
+    pub fn create(id: &str, out_path: &Path, command: &str) -> Result<(Self, fs::File)> {
+        let dir = runs_dir();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Cannot create directory {}", dir.display()))?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path(id))
+            .with_context(|| format!("Cannot open {}", log_path(id).display()))?;
+
+        let entry = RunEntry {
+            id: id.to_string(),
+            pid: std::process::id(),
+            child_pid: None,
+            output_path: fs::canonicalize(out_path.parent().unwrap_or(Path::new(".")))
+                .map(|p| p.join(out_path.file_name().unwrap_or_default()))
+                .unwrap_or_else(|_| out_path.to_path_buf()),
+            start: Utc::now(),
+            command: command.to_string(),
+        };
+        entry.save()?;
+        Ok((Registration { entry }, log))
+    }
+
+    /// Record the pid of the spawned command. If that fails, the command is
+    /// killed: `status` and `stop` could not reach it.
+    pub fn started(&mut self, child: &mut std::process::Child) -> Result<()> {
+        self.entry.child_pid = Some(child.id());
+        let saved = self.entry.save();
+        if saved.is_err() {
+            // The command runs in its own process group (see `execute`)
+            #[cfg(unix)]
+            let _ = crate::signals::signal_group(child.id(), libc::SIGKILL);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        saved
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // Log first: once the entry is gone, `attach` may already be done
+        let _ = fs::remove_file(log_path(&self.entry.id));
+        let _ = fs::remove_file(entry_path(&self.entry.id));
+    }
+}
+
+/// `rommy run --detach`: start a supervisor (`rommy run --detached-id ID ...`) in its own
+/// session and return as soon as it has registered the run.
+#[cfg(unix)]
+pub fn start(cfg: RunConfig) -> Result<()> {
+    use std::ffi::OsString;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let colors = color_is_enabled(cfg.color);
+
+    // The editor has to run here, in the foreground
+    let scratch = if cfg.script.is_none() && cfg.cmd.is_empty() {
+        Some(crate::scratch::launch_editor_and_get_script()?)
+    } else {
+        None
+    };
+
+    let out_path = match &cfg.out {
+        Some(out) => out.clone(),
+        None => {
+            let token = if cfg.script.is_some() || scratch.is_some() {
+                "#!/usr/bin/env bash\n<script>".to_string()
+            } else {
+                format!("$ {}", shell_join(&cfg.cmd)?)
+            };
+            crate::auto_out_path(cfg.compress, &token, chrono::Local::now())?
+        }
+    };
+
+    let id = reserve_id()?;
+
+    // Forward our own arguments, minus --detach, plus what the supervisor must not re-derive.
+    // Everything after `--` is the user's command and stays untouched.
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    let split = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let mut sup_args: Vec<OsString> = args[..split]
+        .iter()
+        .filter(|a| *a != "--detach")
+        .cloned()
+        .collect();
+    sup_args.push("--detached-id".into());
+    sup_args.push(id.clone().into());
+    if cfg.out.is_none() {
+        sup_args.push("--out".into());
+        sup_args.push(out_path.clone().into());
+    }
+    if let Some(script) = &scratch {
+        sup_args.push("--script".into());
+        sup_args.push(script.clone().into());
+    }
+    sup_args.extend(args[split..].iter().cloned());
+
+    let exe = std::env::current_exe().context("Cannot locate rommy executable")?;
+    let mut command = Command::new(exe);
+    command
+        .args(&sup_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe; detaching from the terminal means closing it
+    // does not send SIGHUP to the supervisor.
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    let mut supervisor = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = fs::remove_file(log_path(&id));
+            return Err(e).context("Failed to spawn detached supervisor");
+        }
+    };
+
+    // Wait until the supervisor registered itself, so `rommy status` sees it right away
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !entry_path(&id).exists() && Instant::now() < deadline {
+        if supervisor.try_wait()?.is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    if let Some(status) = supervisor.try_wait()?
+        && !status.success()
+    {
+        let _ = fs::remove_file(log_path(&id));
+        anyhow::bail!("Detached supervisor exited early ({status})");
+    }
+
+    println!("{id}");
+    rommy_note_cyan(
+        colors,
+        &format!(
+            "Started {id} in background; output will be written to {} (rommy attach {id})",
+            out_path.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Pick a short run id and claim it by creating its (empty) live log, so two
+/// runs started in the same instant cannot end up with the same id.
+#[cfg(unix)]
+fn reserve_id() -> Result<String> {
+    let dir = runs_dir();
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Cannot create directory {}", dir.display()))?;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let seed = (nanos as u32) ^ ((nanos >> 32) as u32) ^ std::process::id();
+    for attempt in 0u32..1000 {
+        let id = format!(
+            "{:08x}",
+            seed.wrapping_add(attempt.wrapping_mul(0x9e37_79b9))
+        );
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(log_path(&id))
+        {
+            Ok(_) => return Ok(id),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Cannot create {}", log_path(&id).display()));
+            }
+        }
+    }
+    anyhow::bail!("Cannot find a free run id in {}", dir.display())
+}
+
+#[cfg(not(unix))]
+pub fn start(_cfg: RunConfig) -> Result<()> {
+    anyhow::bail!("--detach is only supported on Unix");
+}
+
+/// All registered runs whose supervisor is still alive. Entries left behind by a
+/// supervisor that died are pruned on the way.
+fn active_runs() -> Result<Vec<RunEntry>> {
+    let dir = runs_dir();
+    let mut runs = Vec::new();
+    let read_dir = match fs::read_dir(&dir) {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(runs),
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", dir.display())),
+    };
+    for entry in read_dir {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(run) = RunEntry::load(&path) else {
+            continue;
+        };
+        if supervisor_alive(run.pid) {
+            runs.push(run);
+        } else {
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(log_path(&run.id));
+        }
+    }
+    prune_orphaned_logs(&dir);
+    runs.sort_by_key(|r| r.start);
+    Ok(runs)
+}
+
+/// Delete live logs that no run is registered for: left behind by a supervisor
+/// that never registered or was killed before it could clean up. Fresh ones may
+/// belong to a run that is still starting.
+fn prune_orphaned_logs(dir: &Path) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("log")
+            || path.with_extension("json").exists()
+        {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > Duration::from_secs(60));
+        if stale {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+fn supervisor_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        crate::signals::pid_alive(pid)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Find an active run by id or unique id prefix.
+fn find_run(id: &str) -> Result<RunEntry> {
+    let mut matches: Vec<RunEntry> = active_runs()?
+        .into_iter()
+        .filter(|r| r.id.starts_with(id))
+        .collect();
+    match matches.len() {
+        0 => anyhow::bail!("No active run with id {id} (see rommy status)"),
+        1 => Ok(matches.remove(0)),
+        n => anyhow::bail!("Id {id} is ambiguous ({n} active runs match)"),
+    }
+}
+
+pub fn status(cfg: StatusConfig) -> Result<()> {
+    let runs = active_runs()?;
+    match cfg.format {
+        StatusFormat::Text => {
+            if runs.is_empty() {
+                println!("No active runs.");
+            }
+            let now = Utc::now();
+            for run in &runs {
+                let elapsed = (now - run.start).num_seconds().max(0);
+                let pid = run
+                    .child_pid
+                    .map_or_else(|| "-".to_string(), |pid| pid.to_string());
+                println!(
+                    "{}  pid {}  {}s  {}  -> {}",
+                    run.id,
+                    pid,
+                    elapsed,
+                    run.command,
+                    run.output_path.display()
+                );
+            }
+        }
+        StatusFormat::Json => {
+            let items: Vec<Value> = runs.iter().map(RunEntry::to_json).collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "runs": items }))?
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Follow the live log of a detached run until its supervisor has written the record.
+pub fn attach(cfg: AttachConfig) -> Result<()> {
+    let colors = color_is_enabled(cfg.color);
+    let run = find_run(&cfg.id)?;
+    let path = log_path(&run.id);
+    // Keep the handle open: the supervisor removes the file when it is done,
+    // but everything written so far stays readable through this handle.
+    let mut log: Box<dyn Read> = match fs::File::open(&path) {
+        Ok(file) => Box::new(file),
+        // Finalised between looking up the run and opening its log
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Box::new(io::empty()),
+        Err(e) => return Err(e).with_context(|| format!("Cannot open {}", path.display())),
+    };
+    let mut stdout = io::stdout();
+    let mut buf = [0u8; 8192];
+    loop {
+        let finished = !entry_path(&run.id).exists() || !supervisor_alive(run.pid);
+        loop {
+            let n = log.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            stdout.write_all(&buf[..n])?;
+        }
+        stdout.flush()?;
+        if finished {
+            break;
+        }
+        thread::sleep(POLL);
+    }
+    rommy_note_cyan(
+        colors,
+        &format!(
+            "Run {} finished; wrote {}",
+            run.id,
+            run.output_path.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Signal the command of a detached run; its supervisor then finalises the record.
+#[cfg(unix)]
+pub fn stop(cfg: StopConfig) -> Result<()> {
+    let colors = color_is_enabled(cfg.color);
+    let run = find_run(&cfg.id)?;
+    let sig = match cfg.signal {
+        StopSignal::Term => libc::SIGTERM,
+        StopSignal::Int => libc::SIGINT,
+        StopSignal::Kill => libc::SIGKILL,
+    };
+    let Some(child_pid) = run.child_pid else {
+        anyhow::bail!("Run {} is still starting; try again", run.id);
+    };
+    crate::signals::signal_group(child_pid, sig)
+        .with_context(|| format!("Cannot signal run {} (pid {})", run.id, child_pid))?;
+
+    let deadline = Instant::now() + Duration::from_secs(cfg.timeout);
+    while entry_path(&run.id).exists() && supervisor_alive(run.pid) {
+        if Instant::now() >= deadline {
+            anyhow::bail!(
+                "Run {} did not stop within {}s; try --signal kill",
+                run.id,
+                cfg.timeout
+            );
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    rommy_note_cyan(
+        colors,
+        &format!("Stopped {}; wrote {}", run.id, run.output_path.display()),
+    );
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn stop(_cfg: StopConfig) -> Result<()> {
+    anyhow::bail!("rommy stop is only supported on Unix");
+}
@@ -0,0 +1,369 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use rommy::parser::RommyRecord;
+use serde_json::{Value, json};
+use similar::{ChangeTag, TextDiff};
+
+use crate::{BOLD, CYAN, DiffConfig, DiffFormat, GREEN, RED, RESET, YELLOW};
+use crate::{color_is_enabled, progress};
+
+/// Number of (inserted, deleted) lines between two texts.
+pub fn line_changes(old: &str, new: &str) -> (usize, usize) {
+    let old = with_final_newline(old);
+    let new = with_final_newline(new);
+    let diff = TextDiff::from_lines(old.as_str(), new.as_str());
+    let mut inserted = 0;
+    let mut deleted = 0;
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => inserted += 1,
+            ChangeTag::Delete => deleted += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (inserted, deleted)
+}
+
+/// Line-based unified diff, or `None` if both texts are identical.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let old = with_final_newline(old);
+    let new = with_final_newline(new);
+    let diff = TextDiff::from_lines(old.as_str(), new.as_str());
+    Some(
+        diff.unified_diff()
+            .context_radius(3)
+            .header(old_name, new_name)
+            .to_string(),
+    )
+}
+
+// Parsed blocks carry no trailing newline; add one so the last line compares like the others.
+fn with_final_newline(s: &str) -> String {
+    if s.is_empty() || s.ends_with('\n') {
+        s.to_string()
+    } else {
+        format!("{s}\n")
+    }
+}
+
+/// Normalisers that mask run-to-run noise before comparing output
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Normalizer {
+    /// ISO 8601 dates/times and clock times
+    Timestamps,
+    /// Paths below /tmp, /var/folders and $TMPDIR
+    TempPaths,
+    /// Durations such as `1.23s` or `450ms`
+    Durations,
+}
+
+impl Normalizer {
+    fn as_str(self) -> &'static str {
+        match self {
+            Normalizer::Timestamps => "timestamps",
+            Normalizer::TempPaths => "temp-paths",
+            Normalizer::Durations => "durations",
+        }
+    }
+}
+
+/// META keys compared between records, in display order
+const META_KEYS: [&str; 5] = ["exit_code", "status", "duration_ms", "cwd", "git_commit"];
+
+/// One compared block (COMMAND, STDOUT or STDERR)
+struct BlockDiff {
+    name: &'static str,
+    old: String,
+    new: String,
+}
+
+impl BlockDiff {
+    fn differs(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+struct MetaDiff {
+    key: &'static str,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+/// `path#N` selects record N (1-based); a bare path selects the last record.
+fn load_record(selector: &str) -> Result<(String, RommyRecord)> {
+    let (path, index) = match selector.rsplit_once('#') {
+        Some((path, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+            (path, Some(n.parse::<usize>()?))
+        }
+        _ => (selector, None),
+    };
+    let mut records =
+        rommy::parser::parse_file(path).with_context(|| format!("failed to parse {path}"))?;
+    anyhow::ensure!(!records.is_empty(), "No records found in {path}");
+    let n = index.unwrap_or(records.len());
+    anyhow::ensure!(
+        n >= 1 && n <= records.len(),
+        "record #{} is out of range in {} (1..={})",
+        n,
+        path,
+        records.len()
+    );
+    Ok((format!("{path}#{n}"), records.swap_remove(n - 1)))
+}
+
+fn compile_normalizers(normalizers: &[Normalizer]) -> Vec<(Regex, &'static str)> {
+    normalizers.iter().flat_map(|n| rules_for(*n)).collect()
+}
+
+fn rules_for(normalizer: Normalizer) -> Vec<(Regex, &'static str)> {
+    let mut rules = Vec::new();
+    match normalizer {
+        Normalizer::Timestamps => {
+            rules.push((
+                Regex::new(
+                    r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?",
+                )
+                .expect("valid timestamp regex"),
+                "<timestamp>",
+            ));
+            rules.push((
+                Regex::new(r"\b\d{1,2}:\d{2}:\d{2}(\.\d+)?\b").expect("valid time regex"),
+                "<timestamp>",
+            ));
+        }
+        Normalizer::TempPaths => {
+            let mut roots = vec![
+                "/tmp/".to_string(),
+                "/private/var/folders/".to_string(),
+                "/var/folders/".to_string(),
+            ];
+            let tmp = std::env::temp_dir().display().to_string();
+            let tmp = format!("{}/", tmp.trim_end_matches('/'));
+            if !roots.contains(&tmp) && tmp.len() > 1 {
+                roots.push(tmp);
+            }
+            let alternatives: Vec<String> = roots.iter().map(|r| regex::escape(r)).collect();
+            rules.push((
+                Regex::new(&format!(r#"(?:{})[^\s'"`:,;)\]]*"#, alternatives.join("|")))
+                    .expect("valid temp path regex"),
+                "<tmp>",
+            ));
+        }
+        Normalizer::Durations => rules.push((
+            Regex::new(r"\b\d+(\.\d+)?\s?(ns|us|µs|ms|s|secs?|seconds?|mins?|minutes?)\b")
+                .expect("valid duration regex"),
+            "<duration>",
+        )),
+    }
+    rules
+}
+
+fn apply_normalizers(text: &str, rules: &[(Regex, &'static str)]) -> String {
+    let mut out = text.to_string();
+    for (re, replacement) in rules {
+        out = re.replace_all(&out, *replacement).into_owned();
+    }
+    out
+}
+
+/// `rommy diff A B`: compare two records. Returns whether they differ.
+pub fn diff(cfg: DiffConfig) -> Result<bool> {
+    let (old_name, old) = load_record(&cfg.a)?;
+    let (new_name, new) = load_record(&cfg.b)?;
+
+    let normalizers: Vec<Normalizer> = if cfg.no_normalize {
+        Vec::new()
+    } else {
+        cfg.normalize.clone()
+    };
+    let rules = compile_normalizers(&normalizers);
+    let ignore_duration = normalizers.contains(&Normalizer::Durations);
+
+    let meta: Vec<MetaDiff> = META_KEYS
+        .iter()
+        .map(|&key| MetaDiff {
+            key,
+            old: old.meta.get(key).cloned(),
+            new: new.meta.get(key).cloned(),
+        })
+        .filter(|m| m.old != m.new)
+        .collect();
+    let blocks = [
+        BlockDiff {
+            name: "COMMAND",
+            old: old.command.clone(),
+            new: new.command.clone(),
+        },
+        BlockDiff {
+            name: "STDOUT",
+            old: apply_normalizers(&old.stdout, &rules),
+            new: apply_normalizers(&new.stdout, &rules),
+        },
+        BlockDiff {
+            name: "STDERR",
+            old: apply_normalizers(&old.stderr, &rules),
+            new: apply_normalizers(&new.stderr, &rules),
+        },
+    ];
+
+    // Durations always differ a little; with the durations normaliser they are shown but not counted
+    let differs = meta
+        .iter()
+        .any(|m| !(ignore_duration && m.key == "duration_ms"))
+        || blocks.iter().any(BlockDiff::differs);
+
+    match cfg.format {
+        DiffFormat::Json => {
+            let meta_items: Vec<Value> = meta
+                .iter()
+                .map(|m| json!({ "key": m.key, "a": m.old, "b": m.new }))
+                .collect();
+            let mut value = json!({
+                "a": old_name,
+                "b": new_name,
+                "identical": !differs,
+                "normalizers": normalizers.iter().map(|n| n.as_str()).collect::<Vec<_>>(),
+                "meta": meta_items,
+            });
+            for block in &blocks {
+                let name_a = format!("a/{}", block.name.to_ascii_lowercase());
+                let name_b = format!("b/{}", block.name.to_ascii_lowercase());
+                value[block.name.to_ascii_lowercase()] =
+                    json!(unified(&block.old, &block.new, &name_a, &name_b));
+            }
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+        DiffFormat::Text | DiffFormat::SideBySide => {
+            let colors = color_is_enabled(cfg.color);
+            let paint = |color: &str, line: &str| {
+                if colors {
+                    format!("{color}{line}{RESET}")
+                } else {
+                    line.to_string()
+                }
+            };
+            println!("{}", paint(BOLD, &format!("--- {old_name}")));
+            println!("{}", paint(BOLD, &format!("+++ {new_name}")));
+            if !differs {
+                println!("Records are identical.");
+            }
+            if !meta.is_empty() {
+                println!("{}", paint(CYAN, "META"));
+                for m in &meta {
+                    let old_v = m.old.as_deref().unwrap_or("(none)");
+                    let new_v = m.new.as_deref().unwrap_or("(none)");
+                    let delta = match (m.key, old_v.parse::<i64>(), new_v.parse::<i64>()) {
+                        ("duration_ms", Ok(a), Ok(b)) => format!(" ({:+} ms)", b - a),
+                        _ => String::new(),
+                    };
+                    println!("  {}: {} -> {}{}", m.key, old_v, new_v, delta);
+                }
+            }
+            for block in blocks.iter().filter(|b| b.differs()) {
+                println!("{}", paint(CYAN, block.name));
+                if cfg.format == DiffFormat::SideBySide {
+                    for (marker, line) in
+                        side_by_side(&block.old, &block.new, progress::terminal_width())
+                    {
+                        let color = match marker {
+                            '<' => RED,
+                            '>' => GREEN,
+                            '|' => YELLOW,
+                            _ => "",
+                        };
+                        println!("{}", paint(color, &line));
+                    }
+                    continue;
+                }
+                let name = block.name.to_ascii_lowercase();
+                let text = unified(
+                    &block.old,
+                    &block.new,
+                    &format!("a/{name}"),
+                    &format!("b/{name}"),
+                )
+                .unwrap_or_default();
+                // The headers above already name both sides
+                for line in text.lines().skip(2) {
+                    let color = if line.starts_with('+') {
+                        GREEN
+                    } else if line.starts_with('-') {
+                        RED
+                    } else if line.starts_with("@@") {
+                        CYAN
+                    } else {
+                        ""
+                    };
+                    println!("{}", paint(color, line));
+                }
+            }
+        }
+    }
+    Ok(differs)
+}
+
+/// Two-column view of the changed hunks: `|` marks changed lines, `<` removed, `>` added.
+fn side_by_side(old: &str, new: &str, width: usize) -> Vec<(char, String)> {
+    let old = with_final_newline(old);
+    let new = with_final_newline(new);
+    let diff = TextDiff::from_lines(old.as_str(), new.as_str());
+    let col = width.saturating_sub(3) / 2;
+    let fit = |s: &str| {
+        let s = s.trim_end_matches(['\n', '\r']);
+        let mut cell: String = s.chars().take(col).collect();
+        let pad = col.saturating_sub(cell.chars().count());
+        cell.extend(std::iter::repeat_n(' ', pad));
+        cell
+    };
+    let mut lines = Vec::new();
+    for (i, group) in diff.grouped_ops(3).iter().enumerate() {
+        if i > 0 {
+            lines.push((' ', format!("{:^width$}", "...", width = col * 2 + 3)));
+        }
+        let mut deleted: Vec<String> = Vec::new();
+        let mut inserted: Vec<String> = Vec::new();
+        let flush = |lines: &mut Vec<(char, String)>,
+                     deleted: &mut Vec<String>,
+                     inserted: &mut Vec<String>| {
+            let n = deleted.len().max(inserted.len());
+            for k in 0..n {
+                let (left, right) = (deleted.get(k), inserted.get(k));
+                let marker = match (left, right) {
+                    (Some(_), Some(_)) => '|',
+                    (Some(_), None) => '<',
+                    _ => '>',
+                };
+                lines.push((
+                    marker,
+                    format!(
+                        "{} {} {}",
+                        fit(left.map(String::as_str).unwrap_or("")),
+                        marker,
+                        fit(right.map(String::as_str).unwrap_or(""))
+                    ),
+                ));
+            }
+            deleted.clear();
+            inserted.clear();
+        };
+        for op in group {
+            for change in diff.iter_changes(op) {
+                match change.tag() {
+                    ChangeTag::Delete => deleted.push(change.value().to_string()),
+                    ChangeTag::Insert => inserted.push(change.value().to_string()),
+                    ChangeTag::Equal => {
+                        flush(&mut lines, &mut deleted, &mut inserted);
+                        let text = fit(change.value());
+                        lines.push((' ', format!("{text}   {text}")));
+                    }
+                }
+            }
+        }
+        flush(&mut lines, &mut deleted, &mut inserted);
+    }
+    lines
+}
@@ -0,0 +1,322 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rommy::writer::RecordLock;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::progress::format_bytes;
+use crate::{CYAN, RESET};
+use crate::{GcConfig, collect_rommy_files, color_is_enabled, config, index, limits, outpath};
+
+/// Which files `gc` may delete. Files are the unit of deletion; a file with
+/// several appended records is judged by its newest record.
+#[derive(Debug, Clone)]
+struct Policy {
+    /// Delete files whose newest record is older than this many seconds
+    older_than: Option<u64>,
+    /// Delete the oldest files until the store is at most this many bytes
+    max_size: Option<u64>,
+    /// Never delete files containing a record that did not succeed
+    keep_failed: bool,
+    /// Never delete files containing a labeled record
+    keep_labeled: bool,
+    /// Never delete the newest N files per command
+    keep_last: Option<usize>,
+}
+
+impl Policy {
+    fn from_cli(cfg: &GcConfig) -> Self {
+        Policy {
+            older_than: cfg.older_than,
+            max_size: cfg.max_size,
+            keep_failed: cfg.keep_failed,
+            keep_labeled: cfg.keep_labeled,
+            keep_last: cfg.keep_last,
+        }
+    }
+
+    /// The `gc.*` settings from `rommy.conf` and the `gc.interval` between
+    /// automatic runs in seconds, or `None` unless `gc.auto: true`.
+    fn from_settings(settings: &HashMap<String, String>) -> Result<Option<(Self, u64)>> {
+        let get = |key: &str| settings.get(key).map(String::as_str);
+        let flag = |key: &str| -> Result<bool> {
+            get(key)
+                .map(config::parse_bool)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{key}: {e}"))
+                .map(|v| v.unwrap_or(false))
+        };
+        if !flag("gc.auto")? {
+            return Ok(None);
+        }
+        let interval = get("gc.interval")
+            .map(limits::parse_secs)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("gc.interval: {e}"))?
+            .unwrap_or(DEFAULT_AUTO_INTERVAL);
+        let policy = Policy {
+            older_than: get("gc.older_than")
+                .map(limits::parse_secs)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("gc.older_than: {e}"))?,
+            max_size: get("gc.max_size")
+                .map(limits::parse_size)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("gc.max_size: {e}"))?,
+            keep_failed: flag("gc.keep_failed")?,
+            keep_labeled: flag("gc.keep_labeled")?,
+            keep_last: get("gc.keep_last")
+                .map(str::parse::<usize>)
+                .transpose()
+                .context("gc.keep_last")?,
+        };
+        Ok(Some((policy, interval)))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.older_than.is_none() && self.max_size.is_none()
+    }
+}
+
+struct StoredFile {
+    path: PathBuf,
+    size: u64,
+    newest: DateTime<Utc>,
+    command: String,
+    failed: bool,
+    labeled: bool,
+}
+
+struct Removal {
+    path: PathBuf,
+    size: u64,
+    reason: &'static str,
+}
+
+fn scan(root: &Path) -> Result<Vec<StoredFile>> {
+    let mut files = Vec::new();
+    if root.exists() {
+        collect_rommy_files(root, &mut files)?;
+    }
+    let index = index::Reader::open();
+    let mut stored = Vec::new();
+    for path in files {
+        let records = match index.as_ref().and_then(|ix| ix.records(&path)) {
+            Some(records) => records,
+            // Files that cannot be parsed are left alone
+            None => match rommy::parser::parse_file(&path) {
+                Ok(records) => records,
+                Err(_) => continue,
+            },
+        };
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
+        };
+        let modified = meta.modified().ok().map(DateTime::<Utc>::from);
+        let newest = records
+            .iter()
+            .filter_map(|r| r.start().ok().flatten())
+            .max()
+            .or(modified);
+        let Some(newest) = newest else {
+            continue;
+        };
+        stored.push(StoredFile {
+            size: meta.len(),
+            newest,
+            command: records
+                .last()
+                .map(|r| r.command.clone())
+                .unwrap_or_default(),
+            failed: records
+                .iter()
+                .any(|r| r.meta.get("status").is_some_and(|s| s != "ok")),
+            labeled: records.iter().any(|r| r.meta.contains_key("label")),
+            path,
+        });
+    }
+    // Newest first
+    stored.sort_by_key(|f| std::cmp::Reverse(f.newest));
+    Ok(stored)
+}
+
+/// Decide which files to delete, oldest first.
+fn plan(
+    files: &[StoredFile],
+    policy: &Policy,
+    now: DateTime<Utc>,
+    keep: Option<&Path>,
+) -> Vec<Removal> {
+    let mut protected: HashSet<&Path> = keep.into_iter().collect();
+    let mut seen_per_command: HashMap<&str, usize> = HashMap::new();
+    for f in files {
+        let seen = seen_per_command.entry(f.command.as_str()).or_default();
+        *seen += 1;
+        if (policy.keep_failed && f.failed)
+            || (policy.keep_labeled && f.labeled)
+            || policy.keep_last.is_some_and(|n| *seen <= n)
+        {
+            protected.insert(&f.path);
+        }
+    }
+
+    let mut removals = Vec::new();
+    let mut removed: HashSet<&Path> = HashSet::new();
+    if let Some(secs) = policy.older_than {
+        let cutoff = now - chrono::Duration::seconds(secs as i64);
+        for f in files.iter().rev() {
+            if f.newest < cutoff && !protected.contains(f.path.as_path()) {
+                removed.insert(&f.path);
+                removals.push(Removal {
+                    path: f.path.clone(),
+                    size: f.size,
+                    reason: "older than limit",
+                });
+            }
+        }
+    }
+    if let Some(max) = policy.max_size {
+        let mut total: u64 = files
+            .iter()
+            .filter(|f| !removed.contains(f.path.as_path()))
+            .map(|f| f.size)
+            .sum();
+        for f in files.iter().rev() {
+            if total <= max {
+                break;
+            }
+            if removed.contains(f.path.as_path()) || protected.contains(f.path.as_path()) {
+                continue;
+            }
+            total -= f.size;
+            removals.push(Removal {
+                path: f.path.clone(),
+                size: f.size,
+                reason: "store over size limit",
+            });
+        }
+    }
+    removals
+}
+
+/// Delete empty directories below `root` (the YYYY/MM/DD layout), never `root` itself.
+fn remove_empty_dirs(dir: &Path, root: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_date_dir = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+        if is_date_dir && path.is_dir() {
+            removed += remove_empty_dirs(&path, root);
+        }
+    }
+    if dir != root && fs::remove_dir(dir).is_ok() {
+        removed += 1;
+    }
+    removed
+}
+
+/// Apply `policy` to the store; returns the removals (performed unless `dry_run`).
+/// `keep` is never removed.
+fn collect(policy: &Policy, dry_run: bool, keep: Option<&Path>) -> Result<Vec<Removal>> {
+    let root = outpath::default_root_dir();
+    // Canonical root, so scanned paths compare equal to the canonical `keep`
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let keep = keep.and_then(|p| fs::canonicalize(p).ok());
+    let files = scan(&root)?;
+    let removals = plan(&files, policy, Utc::now(), keep.as_deref());
+    if dry_run {
+        return Ok(removals);
+    }
+    let mut done = Vec::with_capacity(removals.len());
+    for r in removals {
+        // Wait for a concurrent `run --append`, then make sure it did not change the file
+        let lock = RecordLock::acquire(&r.path)?;
+        if fs::metadata(&r.path).map(|m| m.len()).ok() != Some(r.size) {
+            continue;
+        }
+        index::forget(std::slice::from_ref(&r.path))?;
+        fs::remove_file(&r.path).with_context(|| format!("Cannot remove {}", r.path.display()))?;
+        // The lock file would keep the date directory alive
+        lock.remove();
+        done.push(r);
+    }
+    remove_empty_dirs(&root, &root);
+    Ok(done)
+}
+
+/// `rommy gc`: delete old recordings from the output root.
+pub fn gc(cfg: GcConfig) -> Result<()> {
+    let policy = Policy::from_cli(&cfg);
+    anyhow::ensure!(
+        !policy.is_empty(),
+        "Nothing to do: give --older-than and/or --max-size"
+    );
+    let colors = color_is_enabled(cfg.color);
+    let removals = collect(&policy, cfg.dry_run, None)?;
+    let verb = if cfg.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    for r in &removals {
+        println!(
+            "{} {} ({}, {})",
+            verb,
+            r.path.display(),
+            format_bytes(r.size as usize),
+            r.reason
+        );
+    }
+    let total: u64 = removals.iter().map(|r| r.size).sum();
+    let summary = format!(
+        "{} {} file(s), {}{}",
+        verb,
+        removals.len(),
+        format_bytes(total as usize),
+        if cfg.dry_run { " (dry run)" } else { "" }
+    );
+    if colors {
+        println!("{CYAN}{summary}{RESET}");
+    } else {
+        println!("{summary}");
+    }
+    Ok(())
+}
+
+/// Seconds between automatic runs unless `gc.interval` says otherwise.
+const DEFAULT_AUTO_INTERVAL: u64 = 3600;
+
+/// Run the `gc.*` policy from `rommy.conf` after a run, if `gc.auto` is enabled
+/// and the last automatic run is at least `gc.interval` ago, sparing the record
+/// just written. Returns the number of files and bytes removed.
+pub fn auto(just_written: &Path) -> Result<Option<(usize, u64)>> {
+    let Some((policy, interval)) = Policy::from_settings(&config::load()?)? else {
+        return Ok(None);
+    };
+    if policy.is_empty() {
+        return Ok(None);
+    }
+    // The stamp's mtime is the time of the last automatic run
+    let stamp = outpath::default_root_dir().join(".gc-last");
+    let recent = fs::metadata(&stamp)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < interval);
+    if recent {
+        return Ok(None);
+    }
+    fs::write(&stamp, b"").with_context(|| format!("Cannot write {}", stamp.display()))?;
+    let removals = collect(&policy, false, Some(just_written))?;
+    Ok(Some((
+        removals.len(),
+        removals.iter().map(|r| r.size).sum(),
+    )))
+}
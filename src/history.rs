@@ -0,0 +1,476 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::{Regex, RegexBuilder};
+use rommy::parser::RommyRecord;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{CYAN, LsConfig, LsFormat, RED, RESET, YELLOW};
+use crate::{SearchBlock, SearchConfig, SearchFormat};
+use crate::{collect_rommy_files, color_is_enabled, index, limits, outpath};
+
+/// One record found while walking the store.
+struct Entry {
+    path: PathBuf,
+    record: usize,
+    records_in_file: usize,
+    start: DateTime<Utc>,
+    data: RommyRecord,
+}
+
+impl Entry {
+    fn reference(&self) -> String {
+        if self.records_in_file > 1 {
+            format!("{}#{}", self.path.display(), self.record)
+        } else {
+            self.path.display().to_string()
+        }
+    }
+
+    fn meta(&self, key: &str) -> Option<&str> {
+        self.data.meta.get(key).map(String::as_str)
+    }
+}
+
+/// Parse `--since`/`--until`: a relative age (`30m`, `2h`, `2d`), a date
+/// (`2025-10-26`, local midnight), a local date and time, or RFC 3339.
+pub fn parse_time_bound(s: &str) -> Result<DateTime<Utc>, String> {
+    let t = s.trim();
+    if let Ok(secs) = limits::parse_secs(t) {
+        return Ok(Utc::now() - chrono::Duration::seconds(secs as i64));
+    }
+    if let Ok(ts) = DateTime::parse_from_rfc3339(t) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let local = |naive: NaiveDateTime| {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|ts| ts.with_timezone(&Utc))
+    };
+    for fmt in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(t, fmt)
+            && let Some(ts) = local(naive)
+        {
+            return Ok(ts);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(t, "%Y-%m-%d")
+        && let Some(ts) = date.and_hms_opt(0, 0, 0).and_then(local)
+    {
+        return Ok(ts);
+    }
+    Err(format!(
+        "invalid time '{s}' (expected e.g. 2d, 12h, 2025-10-26 or 2025-10-26T17:30)"
+    ))
+}
+
+/// Every record below `paths`, newest first, plus the number of files that failed to parse.
+fn load_entries(paths: &[PathBuf]) -> Result<(Vec<Entry>, usize)> {
+    let mut files = Vec::new();
+    for path in paths {
+        // A fresh output root does not exist until the first run
+        if path.exists() {
+            collect_rommy_files(path, &mut files)?;
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    // Files the index holds in their current state are not parsed again
+    let index = index::Reader::open();
+    let mut entries = Vec::new();
+    let mut unreadable = 0usize;
+    for file in files {
+        let records = match index.as_ref().and_then(|ix| ix.records(&file)) {
+            Some(records) => records,
+            None => match rommy::parser::parse_file(&file) {
+                Ok(records) => records,
+                Err(_) => {
+                    unreadable += 1;
+                    continue;
+                }
+            },
+        };
+        let records_in_file = records.len();
+        for (i, record) in records.into_iter().enumerate() {
+            let Some(start) = record_start(&record, &file) else {
+                continue;
+            };
+            entries.push(Entry {
+                path: file.clone(),
+                record: i + 1,
+                records_in_file,
+                start,
+                data: record,
+            });
+        }
+    }
+    entries.sort_by(|a, b| b.start.cmp(&a.start).then(b.record.cmp(&a.record)));
+    Ok((entries, unreadable))
+}
+
+/// File and 1-based record number of the newest record in the output root.
+pub fn latest_record() -> Result<(PathBuf, usize)> {
+    let root = outpath::default_root_dir();
+    let (entries, _) = load_entries(std::slice::from_ref(&root))?;
+    let newest = entries
+        .into_iter()
+        .next()
+        .with_context(|| format!("No records found in {}", root.display()))?;
+    Ok((newest.path, newest.record))
+}
+
+/// `rommy ls`: list records in the output root (or PATH), newest first.
+pub fn ls(cfg: LsConfig) -> Result<()> {
+    let root = cfg.path.clone().unwrap_or_else(outpath::default_root_dir);
+    let (mut entries, unreadable) = load_entries(std::slice::from_ref(&root))?;
+
+    let grep = cfg
+        .grep_command
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .context("invalid --grep-command pattern")?;
+    let cwd_filter = cfg
+        .cwd
+        .as_ref()
+        .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()));
+
+    entries.retain(|e| {
+        cfg.since.is_none_or(|since| e.start >= since)
+            && cfg.until.is_none_or(|until| e.start <= until)
+            && cfg
+                .label
+                .as_deref()
+                .is_none_or(|label| e.meta("label") == Some(label))
+            && cfg
+                .status
+                .as_deref()
+                .is_none_or(|status| e.meta("status") == Some(status))
+            && cwd_filter.as_deref().is_none_or(|dir| {
+                e.meta("cwd")
+                    .is_some_and(|cwd| Path::new(cwd).starts_with(dir))
+            })
+            && grep.as_ref().is_none_or(|re| re.is_match(&e.data.command))
+    });
+    if let Some(limit) = cfg.limit {
+        entries.truncate(limit);
+    }
+
+    match cfg.format {
+        LsFormat::Text => {
+            let colors = color_is_enabled(cfg.color);
+            if entries.is_empty() {
+                println!("No matching records in {}.", root.display());
+            }
+            for e in &entries {
+                let time = e.start.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+                let duration = e
+                    .meta("duration_ms")
+                    .and_then(|ms| ms.parse::<u64>().ok())
+                    .map(format_duration)
+                    .unwrap_or_else(|| "?".to_string());
+                let exit = e.meta("exit_code").unwrap_or("?");
+                let exit = format!("exit {exit:>3}");
+                let exit = if colors && e.meta("status") != Some("ok") {
+                    format!("{YELLOW}{exit}{RESET}")
+                } else {
+                    exit
+                };
+                let label = e
+                    .meta("label")
+                    .map(|l| format!("[{l}]  "))
+                    .unwrap_or_default();
+                println!("{time}  {duration:>7}  {exit}  {label}{}", e.reference());
+            }
+        }
+        LsFormat::Json => {
+            let items: Vec<Value> = entries
+                .iter()
+                .map(|e| {
+                    json!({
+                        "path": e.path.display().to_string(),
+                        "record": e.record,
+                        "start_ts": e.start.to_rfc3339(),
+                        "duration_ms": e.meta("duration_ms").and_then(|v| v.parse::<u64>().ok()),
+                        "exit_code": e.meta("exit_code").and_then(|v| v.parse::<i64>().ok()),
+                        "status": e.meta("status"),
+                        "label": e.meta("label"),
+                        "cwd": e.meta("cwd"),
+                        "command": e.data.command,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({
+                    "root": root.display().to_string(),
+                    "records": items,
+                }))?
+            );
+        }
+    }
+    if unreadable > 0 {
+        eprintln!("Skipped {unreadable} unreadable file(s) (see rommy validate)");
+    }
+    Ok(())
+}
+
+/// `start_ts` of `record`, or the modification time of its file.
+fn record_start(record: &RommyRecord, file: &Path) -> Option<DateTime<Utc>> {
+    record
+        .meta
+        .get("start_ts")
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| ts.with_timezone(&Utc))
+        .or_else(|| modified(file))
+}
+
+fn modified(path: &Path) -> Option<DateTime<Utc>> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(DateTime::<Utc>::from)
+}
+
+fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{ms}ms")
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m{:02}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+/// A matching line and its context within one block of one record.
+struct Hit {
+    path: PathBuf,
+    record: usize,
+    start: DateTime<Utc>,
+    block: SearchBlock,
+    /// 1-based line number within the block
+    line: usize,
+    before: Vec<(usize, String)>,
+    text: String,
+    after: Vec<(usize, String)>,
+}
+
+impl Hit {
+    fn reference(&self) -> String {
+        format!(
+            "{}#{}:{}",
+            self.path.display(),
+            self.record,
+            self.block.as_str()
+        )
+    }
+}
+
+/// Lines of `block` in `record`; META is searched as `key: value` lines in file order.
+fn block_lines(record: &RommyRecord, block: SearchBlock) -> Vec<std::borrow::Cow<'_, str>> {
+    let text = match block {
+        SearchBlock::Meta => {
+            return record
+                .meta
+                .iter()
+                .map(|(k, v)| format!("{k}: {v}").into())
+                .collect();
+        }
+        SearchBlock::Command => &record.command,
+        SearchBlock::Stdout => &record.stdout,
+        SearchBlock::Stderr => &record.stderr,
+    };
+    text.lines().map(Into::into).collect()
+}
+
+/// Search the records of one file, one record at a time. `None` if the file
+/// cannot be read or parsed.
+fn search_file(
+    file: &Path,
+    index: Option<&index::Reader>,
+    re: &Regex,
+    blocks: &[SearchBlock],
+    context: usize,
+) -> Option<Vec<Hit>> {
+    let records: Box<dyn Iterator<Item = Result<RommyRecord>>> =
+        match index.and_then(|ix| ix.records(file)) {
+            Some(records) => Box::new(records.into_iter().map(Ok)),
+            None => Box::new(
+                rommy::parser::RecordReader::open(file)
+                    .ok()?
+                    .map(|r| r.map_err(Into::into)),
+            ),
+        };
+    let mut hits = Vec::new();
+    for (i, record) in records.enumerate() {
+        let record = record.ok()?;
+        let Some(start) = record_start(&record, file) else {
+            continue;
+        };
+        for block in blocks {
+            let lines = block_lines(&record, *block);
+            let numbered = |range: std::ops::Range<usize>| -> Vec<(usize, String)> {
+                range.map(|j| (j + 1, lines[j].to_string())).collect()
+            };
+            for (n, line) in lines.iter().enumerate() {
+                if !re.is_match(line) {
+                    continue;
+                }
+                hits.push(Hit {
+                    path: file.to_path_buf(),
+                    record: i + 1,
+                    start,
+                    block: *block,
+                    line: n + 1,
+                    before: numbered(n.saturating_sub(context)..n),
+                    text: line.to_string(),
+                    after: numbered(n + 1..(n + 1 + context).min(lines.len())),
+                });
+            }
+        }
+    }
+    Some(hits)
+}
+
+/// `rommy search`: regex search through recorded blocks, newest record first.
+/// Files are read one at a time, newest first, until `--limit` matches are found.
+/// Returns whether anything matched.
+pub fn search(cfg: SearchConfig) -> Result<bool> {
+    let paths = if cfg.paths.is_empty() {
+        vec![outpath::default_root_dir()]
+    } else {
+        cfg.paths.clone()
+    };
+    let re = RegexBuilder::new(&cfg.pattern)
+        .case_insensitive(cfg.ignore_case)
+        .build()
+        .context("invalid search pattern")?;
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if path.exists() {
+            collect_rommy_files(path, &mut files)?;
+        }
+    }
+    files.sort();
+    files.dedup();
+    // A file's newest record was written last
+    files.sort_by_cached_key(|f| std::cmp::Reverse(modified(f)));
+
+    let blocks: Vec<SearchBlock> = [
+        SearchBlock::Command,
+        SearchBlock::Stdout,
+        SearchBlock::Stderr,
+        SearchBlock::Meta,
+    ]
+    .into_iter()
+    .filter(|b| cfg.blocks.contains(b))
+    .collect();
+
+    let index = index::Reader::open();
+    let mut hits = Vec::new();
+    let mut unreadable = 0usize;
+    for file in &files {
+        let Some(mut found) = search_file(file, index.as_ref(), &re, &blocks, cfg.context) else {
+            unreadable += 1;
+            continue;
+        };
+        // Newest record first; lines of one record stay in order
+        found.sort_by(|a, b| b.start.cmp(&a.start).then(b.record.cmp(&a.record)));
+        hits.extend(found);
+        if let Some(limit) = cfg.limit
+            && hits.len() >= limit
+        {
+            hits.truncate(limit);
+            break;
+        }
+    }
+
+    match cfg.format {
+        SearchFormat::Text => {
+            let colors = color_is_enabled(cfg.color);
+            let highlight = |text: &str| {
+                if colors {
+                    re.replace_all(text, |c: &regex::Captures| format!("{RED}{}{RESET}", &c[0]))
+                        .into_owned()
+                } else {
+                    text.to_string()
+                }
+            };
+            let paint = |color: &str, text: String| {
+                if colors {
+                    format!("{color}{text}{RESET}")
+                } else {
+                    text
+                }
+            };
+            for (i, hit) in hits.iter().enumerate() {
+                if cfg.context > 0 && i > 0 {
+                    println!("--");
+                }
+                let reference = hit.reference();
+                for (n, text) in &hit.before {
+                    println!("{}-{text}", paint(CYAN, format!("{reference}-{n}")));
+                }
+                if hit.block == SearchBlock::Meta {
+                    println!(
+                        "{}:{}",
+                        paint(CYAN, reference.clone()),
+                        highlight(&hit.text)
+                    );
+                } else {
+                    println!(
+                        "{}:{}",
+                        paint(CYAN, format!("{reference}:{}", hit.line)),
+                        highlight(&hit.text)
+                    );
+                }
+                for (n, text) in &hit.after {
+                    println!("{}-{text}", paint(CYAN, format!("{reference}-{n}")));
+                }
+            }
+        }
+        SearchFormat::Json => {
+            let context = |lines: &[(usize, String)]| -> Vec<Value> {
+                lines
+                    .iter()
+                    .map(|(n, text)| json!({ "line": n, "text": text }))
+                    .collect()
+            };
+            let items: Vec<Value> = hits
+                .iter()
+                .map(|hit| {
+                    json!({
+                        "path": hit.path.display().to_string(),
+                        "record": hit.record,
+                        "start_ts": hit.start.to_rfc3339(),
+                        "block": hit.block.as_str(),
+                        "line": hit.line,
+                        "text": hit.text,
+                        "before": context(&hit.before),
+                        "after": context(&hit.after),
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({
+                    "pattern": cfg.pattern,
+                    "matches": items,
+                }))?
+            );
+        }
+    }
+    if unreadable > 0 {
+        eprintln!("Skipped {unreadable} unreadable file(s) (see rommy validate)");
+    }
+    Ok(!hits.is_empty())
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rommy::meta::Status;
+use rommy::parser::RommyRecord;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{HtmlConfig, ansi, collect_rommy_files};
+
+/// The 16 standard terminal colors (normal, then bright).
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+table.index { border-collapse: collapse; width: 100%; }
+table.index th, table.index td { border-bottom: 1px solid #ddd; padding: .3em .6em; text-align: left; }
+table.index th { cursor: pointer; user-select: none; background: #f4f4f4; }
+table.index th.sorted-asc::after { content: " ▲"; }
+table.index th.sorted-desc::after { content: " ▼"; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+.status-ok { color: #0a7d3b; }
+.status-fail { color: #b3261e; font-weight: bold; }
+.filters { margin: 1em 0; display: flex; gap: 1em; }
+.filters input { flex: 1; padding: .3em; }
+section.record { margin-top: 2em; border-top: 2px solid #ddd; }
+table.meta td { padding: 0 .8em 0 0; vertical-align: top; font-family: monospace; }
+pre { background: #1e1e1e; color: #e5e5e5; padding: .8em; overflow-x: auto; white-space: pre-wrap; }
+summary { cursor: pointer; font-weight: bold; margin: .5em 0; }
+code { font-family: monospace; }
+"#;
+
+const SCRIPT: &str = r#"
+const table = document.querySelector("table.index");
+const rows = () => Array.from(table.tBodies[0].rows);
+table.querySelectorAll("th").forEach((th, col) => {
+  th.addEventListener("click", () => {
+    const asc = !th.classList.contains("sorted-asc");
+    table.querySelectorAll("th").forEach(h => h.classList.remove("sorted-asc", "sorted-desc"));
+    th.classList.add(asc ? "sorted-asc" : "sorted-desc");
+    const key = r => r.cells[col].dataset.sort ?? r.cells[col].textContent;
+    const numeric = th.dataset.type === "num";
+    rows().sort((a, b) => {
+      const x = key(a), y = key(b);
+      const c = numeric ? Number(x) - Number(y) : x.localeCompare(y);
+      return asc ? c : -c;
+    }).forEach(r => table.tBodies[0].appendChild(r));
+  });
+});
+const search = document.getElementById("search");
+const statusSelect = document.getElementById("status");
+function filter() {
+  const q = search.value.toLowerCase();
+  const s = statusSelect.value;
+  rows().forEach(r => {
+    const section = document.getElementById(r.dataset.target);
+    const show = (s === "" || (s === "ok") === (r.dataset.status === "ok"))
+      && (q === "" || section.textContent.toLowerCase().includes(q));
+    r.hidden = !show;
+    section.hidden = !show;
+  });
+}
+search.addEventListener("input", filter);
+statusSelect.addEventListener("change", filter);
+"#;
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Current SGR attributes while rendering colored output.
+#[derive(Clone, Default, PartialEq)]
+struct Sgr {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl Sgr {
+    fn css(&self) -> String {
+        let (fg, bg) = if self.inverse {
+            (
+                Some(self.bg.clone().unwrap_or_else(|| "#1e1e1e".to_string())),
+                Some(self.fg.clone().unwrap_or_else(|| "#e5e5e5".to_string())),
+            )
+        } else {
+            (self.fg.clone(), self.bg.clone())
+        };
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            let _ = write!(css, "color:{fg};");
+        }
+        if let Some(bg) = bg {
+            let _ = write!(css, "background:{bg};");
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.dim {
+            css.push_str("opacity:.7;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+
+    /// Apply the parameters of one `ESC [ ... m` sequence.
+    fn apply(&mut self, params: &str) {
+        let mut codes = params
+            .split([';', ':'])
+            .map(|p| p.parse::<u32>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = Sgr::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(PALETTE[(code - 30) as usize].to_string()),
+                90..=97 => self.fg = Some(PALETTE[(code - 90 + 8) as usize].to_string()),
+                40..=47 => self.bg = Some(PALETTE[(code - 40) as usize].to_string()),
+                100..=107 => self.bg = Some(PALETTE[(code - 100 + 8) as usize].to_string()),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(color_256),
+                        Some(2) => {
+                            let rgb: Vec<u32> = codes.by_ref().take(3).collect();
+                            (rgb.len() == 3)
+                                .then(|| format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]))
+                        }
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// CSS color for an index into the xterm 256-color palette.
+fn color_256(n: u32) -> String {
+    match n {
+        0..=15 => PALETTE[n as usize].to_string(),
+        16..=231 => {
+            let level = |v: u32| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                level(n / 36),
+                level(n / 6 % 6),
+                level(n % 6)
+            )
+        }
+        _ => {
+            let gray = 8 + 10 * (n.min(255) - 232);
+            format!("#{gray:02x}{gray:02x}{gray:02x}")
+        }
+    }
+}
+
+/// Escape `text` for HTML, turning SGR color sequences into styled spans
+/// and dropping all other escape sequences.
+fn render_ansi(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut style = Sgr::default();
+    let mut open = false;
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != 0x1b {
+            i += 1;
+            continue;
+        }
+        out.push_str(&escape(&text[plain_start..i]));
+        let mut len = ansi::escape_len(&bytes[i..]);
+        // A malformed sequence may end inside a multi-byte character
+        while !text.is_char_boundary(i + len) {
+            len += 1;
+        }
+        let seq = &text[i..i + len];
+        if let Some(params) = seq.strip_prefix("\x1b[").and_then(|s| s.strip_suffix('m')) {
+            let mut next = style.clone();
+            next.apply(params);
+            if next != style {
+                if open {
+                    out.push_str("</span>");
+                }
+                let css = next.css();
+                open = !css.is_empty();
+                if open {
+                    let _ = write!(out, "<span style=\"{css}\">");
+                }
+                style = next;
+            }
+        }
+        i += len;
+        plain_start = i;
+    }
+    out.push_str(&escape(&text[plain_start..]));
+    if open {
+        out.push_str("</span>");
+    }
+    out
+}
+
+fn is_ok(record: &RommyRecord) -> bool {
+    match record.exit_code() {
+        Ok(Some(code)) => code == 0,
+        Ok(None) => matches!(record.status(), Ok(Some(Status::Ok))),
+        Err(_) => false,
+    }
+}
+
+fn write_output(out: &mut String, name: &str, plain: &str, verbatim: Option<&str>, open: bool) {
+    let text = verbatim.unwrap_or(plain);
+    let lines = text.lines().count();
+    let _ = writeln!(
+        out,
+        "<details{}><summary>{name} ({lines} line(s))</summary><pre>{}</pre></details>",
+        if open { " open" } else { "" },
+        render_ansi(text)
+    );
+}
+
+fn render(title: &str, records: &[(String, RommyRecord)]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>",
+        escape(title)
+    );
+    let failed = records.iter().filter(|(_, r)| !is_ok(r)).count();
+    let _ = writeln!(
+        out,
+        "<h1>{}</h1>\n<p>{} record(s), {} failed. Generated {}.</p>",
+        escape(title),
+        records.len(),
+        failed,
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    out.push_str(
+        "<div class=\"filters\"><input id=\"search\" type=\"search\" placeholder=\"Filter by command, label or output\">\
+         <select id=\"status\"><option value=\"\">all</option><option value=\"ok\">ok</option>\
+         <option value=\"failed\">failed</option></select></div>\n",
+    );
+
+    // Index table
+    out.push_str(
+        "<table class=\"index\">\n<thead><tr><th data-type=\"num\">#</th><th>record</th>\
+         <th>label</th><th>command</th><th>status</th><th data-type=\"num\">exit</th>\
+         <th data-type=\"num\">duration (ms)</th><th>started</th></tr></thead>\n<tbody>\n",
+    );
+    for (n, (reference, record)) in records.iter().enumerate() {
+        let meta = |key: &str| record.meta.get(key).map(String::as_str).unwrap_or("");
+        let ok = is_ok(record);
+        let _ = writeln!(
+            out,
+            "<tr data-target=\"rec-{id}\" data-status=\"{status_key}\"><td class=\"num\">{id}</td>\
+             <td><a href=\"#rec-{id}\">{reference}</a></td><td>{label}</td>\
+             <td><code>{command}</code></td><td class=\"{status_class}\">{status}</td>\
+             <td class=\"num\" data-sort=\"{exit_sort}\">{exit}</td>\
+             <td class=\"num\" data-sort=\"{duration_sort}\">{duration}</td><td>{start}</td></tr>",
+            id = n + 1,
+            status_key = if ok { "ok" } else { "failed" },
+            reference = escape(reference),
+            label = escape(meta("label")),
+            command = escape(record.command.lines().next().unwrap_or("")),
+            status_class = if ok { "status-ok" } else { "status-fail" },
+            status = escape(meta("status")),
+            exit_sort = meta("exit_code").parse::<i64>().unwrap_or(i64::MIN),
+            exit = escape(meta("exit_code")),
+            duration_sort = meta("duration_ms").parse::<u64>().unwrap_or(0),
+            duration = escape(meta("duration_ms")),
+            start = escape(meta("start_ts")),
+        );
+    }
+    out.push_str("</tbody>\n</table>\n");
+
+    // One section per record
+    for (n, (reference, record)) in records.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "<section class=\"record\" id=\"rec-{}\">\n<h2>{}. {}</h2>",
+            n + 1,
+            n + 1,
+            escape(reference)
+        );
+        out.push_str("<details><summary>META</summary><table class=\"meta\">\n");
+        for (key, value) in record.meta.iter() {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(key),
+                escape(value)
+            );
+        }
+        out.push_str("</table></details>\n");
+        let _ = writeln!(
+            out,
+            "<details open><summary>COMMAND</summary><pre>{}</pre></details>",
+            escape(&record.command)
+        );
+        write_output(
+            &mut out,
+            "STDOUT",
+            &record.stdout,
+            record.stdout_ansi.as_deref(),
+            true,
+        );
+        // stderr is usually noise for passing records
+        write_output(
+            &mut out,
+            "STDERR",
+            &record.stderr,
+            record.stderr_ansi.as_deref(),
+            !is_ok(record),
+        );
+        out.push_str("</section>\n");
+    }
+    let _ = writeln!(out, "<script>{SCRIPT}</script>\n</body>\n</html>");
+    out
+}
+
+/// `rommy export html`: a single self-contained HTML report of all records below PATH...
+pub fn export(cfg: HtmlConfig) -> Result<()> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in &cfg.paths {
+        collect_rommy_files(path, &mut files)?;
+    }
+    files.sort();
+    files.dedup();
+    anyhow::ensure!(!files.is_empty(), "No files found to export");
+
+    let mut records = Vec::new();
+    for file in &files {
+        let parsed = rommy::parser::parse_file(file)
+            .with_context(|| format!("failed to parse {}", file.display()))?;
+        let count = parsed.len();
+        for (i, record) in parsed.into_iter().enumerate() {
+            let reference = if count > 1 {
+                format!("{}#{}", file.display(), i + 1)
+            } else {
+                file.display().to_string()
+            };
+            records.push((reference, record));
+        }
+    }
+
+    let html = render(cfg.title.as_deref().unwrap_or("rommy report"), &records);
+    match &cfg.out {
+        Some(out) => {
+            fs::write(out, html).with_context(|| format!("Cannot write {}", out.display()))?
+        }
+        None => print!("{html}"),
+    }
+    Ok(())
+}
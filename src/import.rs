@@ -0,0 +1,365 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    AnsiMode, ImportConfig, ImportFrom, apply_ansi_mode, auto_out_path, color_is_enabled, index,
+    normalize_stream, rommy_note_cyan,
+};
+use rommy::compression::Compression;
+use rommy::writer::{self, RecordBuilder, RecordLock, RommyCommand};
+
+/// What could be recovered from a foreign recording.
+#[derive(Default)]
+struct Imported {
+    command: Option<String>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    duration_ms: Option<i64>,
+    exit_code: Option<i32>,
+    output: Vec<u8>,
+    /// Format-specific META (terminal size, title, ...)
+    meta: Vec<(String, String)>,
+}
+
+/// `path` with `-n` before its `.rommy` suffix, e.g. `165900.log-2.rommy.zst`.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let at = name.find(".rommy").unwrap_or(name.len());
+    path.with_file_name(format!("{}-{n}{}", &name[..at], &name[at..]))
+}
+
+/// Lock the output file. An existing `--out` is only used with `--append` or
+/// `--force`; an automatic name (one per second) moves on to the next free `-n`.
+fn claim_out_path(path: PathBuf, cfg: &ImportConfig) -> Result<(PathBuf, RecordLock)> {
+    writer::create_parent_dir(&path)?;
+    let mut candidate = path.clone();
+    let mut n = 1;
+    loop {
+        let lock = RecordLock::acquire(&candidate)?;
+        if cfg.append || cfg.force || !candidate.exists() {
+            return Ok((candidate, lock));
+        }
+        anyhow::ensure!(
+            cfg.out.is_none(),
+            "{} already exists (use --append or --force)",
+            candidate.display()
+        );
+        n += 1;
+        candidate = numbered(&path, n);
+    }
+}
+
+/// Value of `KEY="value"` in the bracketed part of a `script` header or footer.
+fn bracket_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let start = line.find(&format!("{key}=\""))? + key.len() + 2;
+    let len = line[start..].find('"')?;
+    Some(&line[start..start + len])
+}
+
+/// Date in a `script` header or footer: ISO 8601 with offset (util-linux 2.35+)
+/// or the older `date(1)` style in local time.
+fn parse_script_time(line: &str, prefix: &str) -> Option<DateTime<Utc>> {
+    let rest = line.strip_prefix(prefix)?;
+    let date = rest.split(" [").next()?.trim();
+    for fmt in ["%Y-%m-%d %H:%M:%S%:z", "%Y-%m-%d %H:%M:%S%z"] {
+        if let Ok(ts) = DateTime::parse_from_str(date, fmt) {
+            return Some(ts.with_timezone(&Utc));
+        }
+    }
+    for fmt in ["%a %b %e %H:%M:%S %Y", "%a %d %b %Y %H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(date, fmt) {
+            return Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|ts| ts.with_timezone(&Utc));
+        }
+    }
+    None
+}
+
+/// Total delay in a `script -t` timing file, classic (`DELAY BYTES`)
+/// or advanced (`TYPE DELAY ...`) format.
+fn timing_duration_ms(timing: &str) -> i64 {
+    let secs: f64 = timing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let first = fields.next()?;
+            let delay = if first.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                fields.next()?
+            } else {
+                first
+            };
+            delay.parse::<f64>().ok()
+        })
+        .sum();
+    (secs * 1000.0).round() as i64
+}
+
+fn from_script(data: &[u8], timing: Option<&str>) -> Imported {
+    let mut imported = Imported::default();
+    let mut body = data;
+
+    // Header: "Script started on <date> [COMMAND="..." TERM="..." COLUMNS="..." LINES="..."]"
+    if body.starts_with(b"Script started on ") {
+        let end = body.iter().position(|&b| b == b'\n').unwrap_or(body.len());
+        let header = String::from_utf8_lossy(&body[..end]).into_owned();
+        imported.start = parse_script_time(&header, "Script started on ");
+        imported.command = bracket_value(&header, "COMMAND").map(str::to_string);
+        for (key, meta) in [
+            ("TERM", "term"),
+            ("COLUMNS", "terminal_cols"),
+            ("LINES", "terminal_rows"),
+        ] {
+            if let Some(value) = bracket_value(&header, key) {
+                imported.meta.push((meta.to_string(), value.to_string()));
+            }
+        }
+        body = &body[(end + 1).min(body.len())..];
+    }
+
+    // Footer: "\nScript done on <date> [COMMAND_EXIT_CODE="0"]"; the leading
+    // newline is script's own, not part of the output
+    let marker = b"\nScript done on ";
+    let footer_at = body
+        .windows(marker.len())
+        .rposition(|w| w == marker)
+        .or_else(|| body.starts_with(&marker[1..]).then_some(0));
+    if let Some(at) = footer_at {
+        let footer = String::from_utf8_lossy(&body[at..]).into_owned();
+        let footer = footer.trim_start().lines().next().unwrap_or_default();
+        imported.end = parse_script_time(footer, "Script done on ");
+        imported.exit_code =
+            bracket_value(footer, "COMMAND_EXIT_CODE").and_then(|c| c.parse().ok());
+        body = &body[..at];
+    }
+
+    imported.duration_ms = timing.map(timing_duration_ms);
+    imported.output = body.to_vec();
+    imported
+}
+
+fn from_asciicast(text: &str) -> Result<Imported> {
+    let mut imported = Imported::default();
+    let mut lines = text
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'));
+    let first = lines.next().context("empty asciicast file")?;
+    // v1 is a single JSON document; v2 and v3 have a header line followed by events
+    let header: Value = match serde_json::from_str(first) {
+        Ok(header) => header,
+        Err(_) => serde_json::from_str(text).context("not an asciicast file")?,
+    };
+    let version = header["version"]
+        .as_u64()
+        .context("asciicast header without version")?;
+
+    imported.command = header["command"].as_str().map(str::to_string);
+    if let Some(title) = header["title"].as_str() {
+        imported.meta.push(("title".to_string(), title.to_string()));
+    }
+    let term = if version >= 3 {
+        &header["term"]
+    } else {
+        &header
+    };
+    let term_type = term["type"].as_str().or(header["env"]["TERM"].as_str());
+    if let Some(term_type) = term_type {
+        imported
+            .meta
+            .push(("term".to_string(), term_type.to_string()));
+    }
+    let cols = term["cols"].as_u64().or(header["width"].as_u64());
+    let rows = term["rows"].as_u64().or(header["height"].as_u64());
+    for (key, value) in [("terminal_cols", cols), ("terminal_rows", rows)] {
+        if let Some(value) = value {
+            imported.meta.push((key.to_string(), value.to_string()));
+        }
+    }
+    if let Some(shell) = header["env"]["SHELL"].as_str() {
+        imported.meta.push(("shell".to_string(), shell.to_string()));
+    }
+    imported.start = header["timestamp"]
+        .as_i64()
+        .and_then(|ts| Utc.timestamp_opt(ts, 0).single());
+
+    let mut output = String::new();
+    let mut elapsed = 0.0;
+    if version == 1 {
+        for frame in header["stdout"].as_array().into_iter().flatten() {
+            elapsed += frame[0].as_f64().unwrap_or(0.0);
+            output.push_str(frame[1].as_str().unwrap_or_default());
+        }
+    } else {
+        for line in lines {
+            let event: Value = serde_json::from_str(line)
+                .with_context(|| format!("invalid asciicast event: {line}"))?;
+            let time = event[0].as_f64().unwrap_or(0.0);
+            // v2 times are absolute, v3 times are intervals since the previous event
+            if version >= 3 {
+                elapsed += time;
+            } else {
+                elapsed = time;
+            }
+            match event[1].as_str() {
+                Some("o") => output.push_str(event[2].as_str().unwrap_or_default()),
+                Some("x") => {
+                    imported.exit_code = event[2].as_str().and_then(|c| c.parse().ok());
+                }
+                _ => {}
+            }
+        }
+    }
+    let duration = header["duration"].as_f64().unwrap_or(elapsed);
+    imported.duration_ms = Some((duration * 1000.0).round() as i64);
+    imported.output = output.into_bytes();
+    Ok(imported)
+}
+
+fn from_log(data: &[u8], path: &Path) -> Imported {
+    // The file's modification time is when the last line was written
+    let end = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+    Imported {
+        end,
+        output: data.to_vec(),
+        ..Imported::default()
+    }
+}
+
+/// `rommy import`: turn a typescript, asciicast or plain log into a .rommy record.
+pub fn import(cfg: ImportConfig) -> Result<()> {
+    let colors = color_is_enabled(cfg.color);
+    anyhow::ensure!(
+        cfg.timing.is_none() || matches!(cfg.from, ImportFrom::Script),
+        "--timing requires --from script"
+    );
+    let data =
+        fs::read(&cfg.path).with_context(|| format!("Cannot read {}", cfg.path.display()))?;
+    let mut imported = match cfg.from {
+        ImportFrom::Script => {
+            let timing = cfg
+                .timing
+                .as_ref()
+                .map(|t| {
+                    fs::read_to_string(t).with_context(|| format!("Cannot read {}", t.display()))
+                })
+                .transpose()?;
+            from_script(&data, timing.as_deref())
+        }
+        ImportFrom::Asciicast => from_asciicast(&String::from_utf8_lossy(&data))
+            .with_context(|| format!("failed to import {}", cfg.path.display()))?,
+        ImportFrom::Log => from_log(&data, &cfg.path),
+    };
+
+    // Fill in whichever of start, end and duration follows from the other two
+    match (imported.start, imported.end, imported.duration_ms) {
+        (Some(start), None, Some(ms)) => {
+            imported.end = Some(start + chrono::Duration::milliseconds(ms))
+        }
+        (None, Some(end), Some(ms)) => {
+            imported.start = Some(end - chrono::Duration::milliseconds(ms))
+        }
+        (Some(start), Some(end), None) => {
+            imported.duration_ms = Some((end - start).num_milliseconds())
+        }
+        _ => {}
+    }
+
+    let source = fs::canonicalize(&cfg.path).unwrap_or_else(|_| cfg.path.clone());
+    let out_path = match &cfg.out {
+        Some(out) => out.clone(),
+        None => {
+            let token = match &imported.command {
+                Some(command) => format!("$ {command}"),
+                None => cfg.from.as_str().to_string(),
+            };
+            let when = imported.start.or(imported.end).unwrap_or_else(Utc::now);
+            auto_out_path(cfg.compress, &token, when.with_timezone(&Local))?
+        }
+    };
+    let (out_path, lock) = claim_out_path(out_path, &cfg)?;
+
+    let mut extra_meta = vec![
+        ("imported_from".to_string(), cfg.from.as_str().to_string()),
+        ("source_path".to_string(), source.display().to_string()),
+    ];
+    extra_meta.append(&mut imported.meta);
+    let output = String::from_utf8_lossy(&imported.output)
+        .into_owned()
+        .into_bytes();
+    let output = if cfg.no_normalize {
+        output
+    } else {
+        normalize_stream("stdout", &output, &mut extra_meta)
+    };
+    let (stdout_bytes, stdout_ansi) = apply_ansi_mode(cfg.ansi, output);
+    if cfg.ansi != AnsiMode::Keep {
+        extra_meta.push(("ansi".to_string(), cfg.ansi.as_str().to_string()));
+    }
+
+    // Same META layout as a recorded run; whatever the source does not tell is listed
+    let mut unknown: Vec<&str> = vec!["cwd", "user", "host"];
+    let mut builder = match &imported.command {
+        Some(command) => RecordBuilder::new(&RommyCommand::Line(command.clone())),
+        None => {
+            unknown.push("command_line");
+            RecordBuilder::without_command()
+        }
+    };
+    if let Some(label) = &cfg.label {
+        builder = builder.label(label);
+    }
+    for (key, value) in [
+        ("start_ts", imported.start.map(|t| t.to_rfc3339())),
+        ("end_ts", imported.end.map(|t| t.to_rfc3339())),
+        ("duration_ms", imported.duration_ms.map(|ms| ms.to_string())),
+    ] {
+        match value {
+            Some(value) => builder = builder.meta(key, value),
+            None => unknown.push(key),
+        }
+    }
+    builder = builder.output_path(&out_path);
+    for (key, value) in extra_meta {
+        builder = builder.meta(key, value);
+    }
+    match imported.exit_code {
+        Some(code) => builder = builder.exit(code, if code == 0 { "ok" } else { "error" }),
+        None => unknown.extend(["status", "exit_code"]),
+    }
+    builder = builder
+        .meta("unknown_fields", unknown.join(", "))
+        .stdout(String::from_utf8_lossy(&stdout_bytes));
+    if let Some(bytes) = &stdout_ansi {
+        builder = builder.stdout_ansi(String::from_utf8_lossy(bytes));
+    }
+    let mut record = Vec::new();
+    writer::write_record(&mut record, &builder.build())?;
+
+    // A recording that happens to contain block markers would not read back
+    rommy::parser::parse_str(&String::from_utf8_lossy(&record))
+        .with_context(|| format!("{} cannot be stored as a .rommy record", cfg.path.display()))?;
+
+    writer::replace_file(
+        &out_path,
+        &Compression::from_path(&out_path).encode(&record)?,
+        cfg.append,
+        &lock,
+    )?;
+    // Still under the lock, as in `run`
+    if let Err(err) = index::update_after_write(&out_path) {
+        rommy_note_cyan(colors, &format!("Index not updated: {err:#}"));
+    }
+    drop(lock);
+    rommy_note_cyan(colors, &format!("Wrote {}", out_path.display()));
+    Ok(())
+}
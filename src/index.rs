@@ -0,0 +1,393 @@
+use anyhow::{Context, Result};
+use rommy::parser::{RecordReader, RommyRecord};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params, types::ValueRef};
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::{QueryConfig, QueryFormat, collect_rommy_files, outpath};
+
+/// SQLite index of the output root: `<root>/index.sqlite`.
+pub fn index_path() -> PathBuf {
+    outpath::default_root_dir().join("index.sqlite")
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    path     TEXT PRIMARY KEY,
+    mtime_ns INTEGER NOT NULL,
+    size     INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS records (
+    id          INTEGER PRIMARY KEY,
+    path        TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE,
+    record      INTEGER NOT NULL,
+    offset      INTEGER,
+    length      INTEGER,
+    start_ts    TEXT,
+    end_ts      TEXT,
+    duration_ms INTEGER,
+    exit_code   INTEGER,
+    status      TEXT,
+    label       TEXT,
+    cwd         TEXT,
+    user        TEXT,
+    host        TEXT,
+    git_commit  TEXT,
+    meta        TEXT NOT NULL,
+    UNIQUE (path, record)
+);
+CREATE INDEX IF NOT EXISTS records_start_ts ON records (start_ts);
+CREATE VIRTUAL TABLE IF NOT EXISTS output USING fts5(command, stdout, stderr);
+CREATE TABLE IF NOT EXISTS blocks (
+    id           INTEGER PRIMARY KEY REFERENCES records(id) ON DELETE CASCADE,
+    meta_entries TEXT NOT NULL,
+    stdout_ansi  TEXT,
+    stderr_ansi  TEXT,
+    timing       TEXT,
+    -- META layout version of the file; `meta_entries` is already upgraded
+    source_version INTEGER NOT NULL
+);
+";
+
+/// Bumped whenever the schema changes; an index of another version is dropped
+/// and refilled as records are written (or by `rommy index rebuild`).
+const SCHEMA_VERSION: i64 = 3;
+
+/// Size and modification time, used to tell whether an indexed file is still current.
+fn stamp(path: &Path) -> Option<(i64, i64)> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    Some((mtime.as_nanos() as i64, meta.len() as i64))
+}
+
+fn key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn open_rw(path: &Path) -> Result<Connection> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create directory {}", parent.display()))?;
+    }
+    let conn =
+        Connection::open(path).with_context(|| format!("Cannot open index {}", path.display()))?;
+    // Several runs may finish at the same time
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version != SCHEMA_VERSION {
+        conn.execute_batch(
+            "DROP TABLE IF EXISTS blocks;
+             DROP TABLE IF EXISTS output;
+             DROP TABLE IF EXISTS records;
+             DROP TABLE IF EXISTS files;",
+        )?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    conn.execute_batch(SCHEMA)
+        .with_context(|| format!("Cannot initialise index {}", path.display()))?;
+    Ok(conn)
+}
+
+/// Records of `text` with the byte offset of each (its `<<<META>>>` line), as
+/// the parser found them.
+fn parse_with_offsets(text: &str) -> Result<Vec<(RommyRecord, usize)>> {
+    let mut reader = RecordReader::new(text.as_bytes());
+    let mut records = Vec::new();
+    while let Some(record) = reader.next() {
+        let record = record?;
+        let start = reader
+            .record_start()
+            .context("parser reported no record start")?;
+        records.push((record, start.offset as usize));
+    }
+    Ok(records)
+}
+
+/// (Re)index one file: replaces whatever the index held for it.
+fn index_file(conn: &mut Connection, path: &Path) -> Result<usize> {
+    let Some((mtime_ns, size)) = stamp(path) else {
+        return Ok(0);
+    };
+    // Offsets refer to the decompressed text
+    let text = rommy::compression::read_to_string(path)?;
+    let records =
+        parse_with_offsets(&text).with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let path_key = key(path);
+    let tx = conn.transaction()?;
+    remove_file_rows(&tx, &path_key)?;
+    tx.execute(
+        "INSERT INTO files (path, mtime_ns, size) VALUES (?1, ?2, ?3)",
+        params![path_key, mtime_ns, size],
+    )?;
+    for (i, (record, offset)) in records.iter().enumerate() {
+        let end = records.get(i + 1).map_or(text.len(), |(_, next)| *next);
+        let length = (end - offset) as i64;
+        let offset = *offset as i64;
+        let meta = |k: &str| record.meta.get(k).map(String::as_str);
+        let int = |k: &str| meta(k).and_then(|v| v.parse::<i64>().ok());
+        tx.execute(
+            "INSERT INTO records (path, record, offset, length, start_ts, end_ts, duration_ms,
+                exit_code, status, label, cwd, user, host, git_commit, meta)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                path_key,
+                (i + 1) as i64,
+                offset,
+                length,
+                meta("start_ts"),
+                meta("end_ts"),
+                int("duration_ms"),
+                int("exit_code"),
+                meta("status"),
+                meta("label"),
+                meta("cwd"),
+                meta("user"),
+                meta("host"),
+                meta("git_commit"),
+                record.meta.to_json().to_string(),
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO output (rowid, command, stdout, stderr) VALUES (?1, ?2, ?3, ?4)",
+            params![id, record.command, record.stdout, record.stderr],
+        )?;
+        // Everything the columns above cannot give back: META order and
+        // duplicates, `--ansi both` and `--timing` blocks
+        tx.execute(
+            "INSERT INTO blocks (id, meta_entries, stdout_ansi, stderr_ansi, timing, source_version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                record.meta.to_json_entries().to_string(),
+                record.stdout_ansi,
+                record.stderr_ansi,
+                record.timing,
+                record.source_version
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(records.len())
+}
+
+fn remove_file_rows(conn: &Connection, path_key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM blocks WHERE id IN (SELECT id FROM records WHERE path = ?1)",
+        params![path_key],
+    )?;
+    conn.execute(
+        "DELETE FROM output WHERE rowid IN (SELECT id FROM records WHERE path = ?1)",
+        params![path_key],
+    )?;
+    conn.execute("DELETE FROM records WHERE path = ?1", params![path_key])?;
+    conn.execute("DELETE FROM files WHERE path = ?1", params![path_key])?;
+    Ok(())
+}
+
+/// Called by `run` after the record has been renamed into place. Only files
+/// inside the output root are indexed.
+pub fn update_after_write(out_path: &Path) -> Result<()> {
+    let root = fs::canonicalize(outpath::default_root_dir());
+    let file = fs::canonicalize(out_path);
+    let (Ok(root), Ok(file)) = (root, file) else {
+        return Ok(());
+    };
+    if !file.starts_with(&root) {
+        return Ok(());
+    }
+    let mut conn = open_rw(&index_path())?;
+    index_file(&mut conn, &file)?;
+    Ok(())
+}
+
+/// Drop files from the index, if there is one. Call before deleting them,
+/// while their canonical paths still resolve.
+pub fn forget(paths: &[PathBuf]) -> Result<()> {
+    let path = index_path();
+    if paths.is_empty() || !path.exists() {
+        return Ok(());
+    }
+    let mut conn = open_rw(&path)?;
+    let tx = conn.transaction()?;
+    for p in paths {
+        remove_file_rows(&tx, &key(p))?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// `rommy index rebuild`: recreate the index from every file in the output root.
+pub fn rebuild() -> Result<()> {
+    let root = outpath::default_root_dir();
+    let path = index_path();
+    for suffix in ["", "-wal", "-shm", "-journal"] {
+        let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+    let mut conn = open_rw(&path)?;
+
+    let mut files = Vec::new();
+    if root.exists() {
+        collect_rommy_files(&root, &mut files)?;
+    }
+    files.sort();
+    let mut records = 0usize;
+    let mut skipped = 0usize;
+    for file in &files {
+        match index_file(&mut conn, file) {
+            Ok(n) => records += n,
+            Err(err) => {
+                skipped += 1;
+                eprintln!("ERR {}: {:#}", file.display(), err);
+            }
+        }
+    }
+    println!(
+        "Indexed {} record(s) from {} file(s) into {}.",
+        records,
+        files.len() - skipped,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Read-only view of the index for `ls`/`search`. `None` if there is no index yet.
+pub struct Reader {
+    conn: Connection,
+}
+
+impl Reader {
+    pub fn open() -> Option<Self> {
+        let conn =
+            Connection::open_with_flags(index_path(), OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+        conn.busy_timeout(Duration::from_secs(5)).ok()?;
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .ok()?;
+        (version == SCHEMA_VERSION).then_some(Reader { conn })
+    }
+
+    /// Records of `path` if the index holds them for the file's current size and mtime.
+    pub fn records(&self, path: &Path) -> Option<Vec<RommyRecord>> {
+        let (mtime_ns, size) = stamp(path)?;
+        let path_key = key(path);
+        self.conn
+            .query_row(
+                "SELECT 1 FROM files WHERE path = ?1 AND mtime_ns = ?2 AND size = ?3",
+                params![path_key, mtime_ns, size],
+                |_| Ok(()),
+            )
+            .optional()
+            .ok()??;
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT b.meta_entries, o.command, o.stdout, o.stderr,
+                        b.stdout_ansi, b.stderr_ansi, b.timing, b.source_version
+                 FROM records r JOIN output o ON o.rowid = r.id JOIN blocks b ON b.id = r.id
+                 WHERE r.path = ?1 ORDER BY r.record",
+            )
+            .ok()?;
+        let rows = stmt
+            .query_map(params![path_key], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, u32>(7)?,
+                ))
+            })
+            .ok()?;
+        let mut records = Vec::new();
+        for row in rows {
+            let (meta, command, stdout, stderr, stdout_ansi, stderr_ansi, timing, source_version) =
+                row.ok()?;
+            let meta: Vec<(String, String)> = serde_json::from_str(&meta).ok()?;
+            records.push(RommyRecord {
+                meta: meta.into_iter().collect(),
+                command,
+                stdout,
+                stderr,
+                stdout_ansi,
+                stderr_ansi,
+                timing,
+                source_version,
+            });
+        }
+        Some(records)
+    }
+}
+
+/// `rommy query SQL`: run ad-hoc SQL against the index.
+pub fn query(cfg: QueryConfig) -> Result<()> {
+    let path = index_path();
+    anyhow::ensure!(
+        path.exists(),
+        "No index at {} (run `rommy index rebuild`)",
+        path.display()
+    );
+    let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Cannot open index {}", path.display()))?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    let mut stmt = conn.prepare(&cfg.sql).context("invalid query")?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+    let mut rows_out: Vec<Vec<Value>> = Vec::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            values.push(match row.get_ref(i)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(n) => json!(n),
+                ValueRef::Real(f) => json!(f),
+                ValueRef::Text(t) => json!(String::from_utf8_lossy(t)),
+                ValueRef::Blob(b) => json!(format!("<{} byte blob>", b.len())),
+            });
+        }
+        rows_out.push(values);
+    }
+
+    match cfg.format {
+        QueryFormat::Text => {
+            println!("{}", columns.join("\t"));
+            for values in &rows_out {
+                let cells: Vec<String> = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Null => "NULL".to_string(),
+                        Value::String(s) => s.replace(['\t', '\n'], " "),
+                        other => other.to_string(),
+                    })
+                    .collect();
+                println!("{}", cells.join("\t"));
+            }
+        }
+        QueryFormat::Json => {
+            let items: Vec<Value> = rows_out
+                .into_iter()
+                .map(|values| {
+                    let object: Map<String, Value> = columns.iter().cloned().zip(values).collect();
+                    Value::Object(object)
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&items)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use rommy::parser::RommyRecord;
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::{JunitConfig, JunitGroup, ansi, collect_rommy_files};
+
+/// One `<testsuite>`: its name and the records it holds, in file order.
+struct Suite {
+    name: String,
+    cases: Vec<RommyRecord>,
+}
+
+/// Escape text for XML, dropping characters XML 1.0 cannot represent
+/// (ANSI escapes are removed before that).
+fn xml_escape(text: &str) -> String {
+    let text = ansi::strip_str(text);
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn seconds(record: &RommyRecord) -> f64 {
+    record
+        .meta
+        .get("duration_ms")
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .map_or(0.0, |ms| ms as f64 / 1000.0)
+}
+
+/// Test case name: the label, else the first line of the command.
+fn case_name(record: &RommyRecord) -> String {
+    if let Some(label) = record.label() {
+        return label.to_string();
+    }
+    let line = record.command.lines().next().unwrap_or_default();
+    line.strip_prefix("$ ").unwrap_or(line).to_string()
+}
+
+/// `Some(message)` if the record counts as a failed test.
+fn failure(record: &RommyRecord) -> Option<String> {
+    let exit_code = record.meta.get("exit_code").map(String::as_str);
+    let status = record.meta.get("status").map(String::as_str);
+    if exit_code == Some("0") || (exit_code.is_none() && status == Some("ok")) {
+        return None;
+    }
+    let mut message = format!("exit code {}", exit_code.unwrap_or("?"));
+    if let Some(status) = status.filter(|s| *s != "error") {
+        let _ = write!(message, " ({status})");
+    }
+    Some(message)
+}
+
+fn write_case(out: &mut String, suite: &str, record: &RommyRecord, tail_lines: usize) {
+    let _ = writeln!(
+        out,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+        xml_escape(&case_name(record)),
+        xml_escape(suite),
+        seconds(record)
+    );
+    if let Some(message) = failure(record) {
+        let lines: Vec<&str> = record.stderr.lines().collect();
+        let tail = lines[lines.len().saturating_sub(tail_lines)..].join("\n");
+        let _ = writeln!(
+            out,
+            "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+            xml_escape(&message),
+            xml_escape(record.meta.get("status").map_or("error", String::as_str)),
+            xml_escape(&tail)
+        );
+    }
+    for (element, text) in [
+        ("system-out", &record.stdout),
+        ("system-err", &record.stderr),
+    ] {
+        if !text.is_empty() {
+            let _ = writeln!(out, "      <{element}>{}</{element}>", xml_escape(text));
+        }
+    }
+    out.push_str("    </testcase>\n");
+}
+
+fn render(suites: &[Suite], tail_lines: usize) -> String {
+    let count = |suite: &Suite| {
+        let failures = suite.cases.iter().filter(|r| failure(r).is_some()).count();
+        let time: f64 = suite.cases.iter().map(seconds).sum();
+        (suite.cases.len(), failures, time)
+    };
+    let (tests, failures, time) = suites.iter().map(count).fold((0, 0, 0.0), |acc, c| {
+        (acc.0 + c.0, acc.1 + c.1, acc.2 + c.2)
+    });
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites name=\"rommy\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" time=\"{time:.3}\">"
+    );
+    for suite in suites {
+        let (tests, failures, time) = count(suite);
+        let _ = write!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"0\" skipped=\"0\" time=\"{time:.3}\"",
+            xml_escape(&suite.name)
+        );
+        if let Some(start) = suite.cases.first().and_then(|r| r.meta.get("start_ts")) {
+            let _ = write!(out, " timestamp=\"{}\"", xml_escape(start));
+        }
+        out.push_str(">\n");
+        for record in &suite.cases {
+            write_case(&mut out, &suite.name, record, tail_lines);
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+/// `rommy export junit`: one `<testcase>` per record, grouped into suites by file or label.
+pub fn export(cfg: JunitConfig) -> Result<()> {
+    let mut files = Vec::new();
+    for path in &cfg.paths {
+        collect_rommy_files(path, &mut files)?;
+    }
+    files.sort();
+    files.dedup();
+    anyhow::ensure!(!files.is_empty(), "No files found to export");
+
+    let mut suites: Vec<Suite> = Vec::new();
+    for file in &files {
+        let records = rommy::parser::parse_file(file)
+            .with_context(|| format!("failed to parse {}", file.display()))?;
+        for record in records {
+            let name = match cfg.group_by {
+                JunitGroup::File => file.display().to_string(),
+                JunitGroup::Label => record
+                    .meta
+                    .get("label")
+                    .cloned()
+                    .unwrap_or_else(|| "unlabeled".to_string()),
+            };
+            match suites.iter_mut().find(|s| s.name == name) {
+                Some(suite) => suite.cases.push(record),
+                None => suites.push(Suite {
+                    name,
+                    cases: vec![record],
+                }),
+            }
+        }
+    }
+
+    let xml = render(&suites, cfg.tail_lines);
+    match &cfg.out {
+        Some(out) => {
+            fs::write(out, xml).with_context(|| format!("Cannot write {}", out.display()))?
+        }
+        None => print!("{xml}"),
+    }
+    Ok(())
+}
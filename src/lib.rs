@@ -1 +1,4 @@
+pub mod compression;
+pub mod meta;
 pub mod parser;
+pub mod writer;
@@ -0,0 +1,236 @@
+use anyhow::Result;
+use std::process::Command;
+
+/// Per-run resource limits (`--limit-*`), applied to the child via setrlimit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Address space in bytes (RLIMIT_AS)
+    pub mem: Option<u64>,
+    /// CPU time in seconds (RLIMIT_CPU)
+    pub cpu: Option<u64>,
+    /// Largest file the command may write, in bytes (RLIMIT_FSIZE)
+    pub fsize: Option<u64>,
+    /// Number of processes for the user (RLIMIT_NPROC)
+    pub nproc: Option<u64>,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    /// META entries describing the configured limits.
+    pub fn meta(&self) -> Vec<(String, String)> {
+        [
+            ("limit_mem_bytes", self.mem),
+            ("limit_cpu_secs", self.cpu),
+            ("limit_fsize_bytes", self.fsize),
+            ("limit_nproc", self.nproc),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| (k.to_string(), v.to_string())))
+        .collect()
+    }
+
+    /// Install a pre-exec hook that applies the limits in the child before exec.
+    #[cfg(unix)]
+    pub fn apply(&self, command: &mut Command) -> Result<()> {
+        use std::os::unix::process::CommandExt;
+
+        if self.is_empty() {
+            return Ok(());
+        }
+        let limits = *self;
+        // SAFETY: the hook only calls getrlimit/setrlimit, which are async-signal-safe,
+        // and does not allocate.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(bytes) = limits.mem {
+                    set_limit(libc::RLIMIT_AS, bytes, bytes)?;
+                }
+                if let Some(secs) = limits.cpu {
+                    // Soft limit sends SIGXCPU, the hard limit one second later SIGKILL
+                    set_limit(libc::RLIMIT_CPU, secs, secs.saturating_add(1))?;
+                }
+                if let Some(bytes) = limits.fsize {
+                    set_limit(libc::RLIMIT_FSIZE, bytes, bytes)?;
+                }
+                if let Some(n) = limits.nproc {
+                    set_limit(libc::RLIMIT_NPROC, n, n)?;
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _command: &mut Command) -> Result<()> {
+        anyhow::ensure!(
+            self.is_empty(),
+            "--limit-* options are only supported on Unix"
+        );
+        Ok(())
+    }
+
+    /// Which limit, if any, most likely ended the command. Only limits that
+    /// were set are considered. Signals are decisive (SIGXCPU, SIGXFSZ); memory
+    /// and process limits only surface as failed allocations or forks, so those
+    /// need the allocator's or errno's message on the line the command died
+    /// with, or anywhere in stderr if it was aborted (SIGABRT, SIGSEGV).
+    pub fn exceeded(
+        &self,
+        exit_code: i32,
+        signal: Option<i32>,
+        cpu_used_secs: Option<f64>,
+        stderr: &[u8],
+    ) -> Option<&'static str> {
+        if exit_code == 0 {
+            return None;
+        }
+        // bash reports a child killed by a signal as 128+N
+        let sig = signal.or_else(|| (exit_code > 128).then_some(exit_code - 128));
+
+        #[cfg(unix)]
+        let aborted = {
+            if self.cpu.is_some() && sig == Some(libc::SIGXCPU) {
+                return Some("limit_cpu");
+            }
+            if self.fsize.is_some() && sig == Some(libc::SIGXFSZ) {
+                return Some("limit_fsize");
+            }
+            if let (Some(limit), Some(used), Some(libc::SIGKILL)) = (self.cpu, cpu_used_secs, sig)
+                && used >= limit as f64
+            {
+                return Some("limit_cpu");
+            }
+            matches!(sig, Some(libc::SIGABRT | libc::SIGSEGV))
+        };
+        #[cfg(not(unix))]
+        let aborted = {
+            let _ = (sig, cpu_used_secs);
+            false
+        };
+
+        let stderr = String::from_utf8_lossy(stderr);
+        let last_line = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("");
+        let evidence = if aborted { &*stderr } else { last_line };
+        if self.mem.is_some()
+            && [
+                // strerror(ENOMEM)
+                "Cannot allocate memory",
+                // Rust's and C++'s allocation failure handlers
+                "memory allocation of",
+                "bad_alloc",
+                "MemoryError",
+            ]
+            .iter()
+            .any(|needle| evidence.contains(needle))
+        {
+            return Some("limit_mem");
+        }
+        // strerror(EAGAIN) from fork(2)
+        if self.nproc.is_some()
+            && evidence.contains("fork")
+            && evidence.contains("Resource temporarily unavailable")
+        {
+            return Some("limit_nproc");
+        }
+        None
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+fn set_limit(resource: Resource, soft: u64, hard: u64) -> std::io::Result<()> {
+    // SAFETY: rlimit is plain data and both calls only read/write it.
+    unsafe {
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if libc::getrlimit(resource, &mut current) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // An unprivileged process cannot raise its hard limit, so stay below it
+        let hard = (hard as libc::rlim_t).min(current.rlim_max);
+        let soft = (soft as libc::rlim_t).min(hard);
+        let new = libc::rlimit {
+            rlim_cur: soft,
+            rlim_max: hard,
+        };
+        if libc::setrlimit(resource, &new) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// CPU time (user + system) consumed by waited-for children of this process.
+pub fn children_cpu_secs() -> Option<f64> {
+    #[cfg(unix)]
+    {
+        // SAFETY: rusage is plain data filled in by the kernel.
+        unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) != 0 {
+                return None;
+            }
+            let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1e6;
+            Some(secs(usage.ru_utime) + secs(usage.ru_stime))
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Parse sizes like `512M`, `2G`, `1GiB` or plain bytes (binary units).
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let split = t
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let value: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size '{s}' (expected e.g. 512M, 2G)"))?;
+    let factor: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        other => return Err(format!("unknown size unit '{other}' in '{s}'")),
+    };
+    Ok((value * factor as f64) as u64)
+}
+
+/// Parse durations like `90`, `60s`, `5m`, `2h`, `1d` into seconds.
+pub fn parse_secs(s: &str) -> Result<u64, String> {
+    let t = s.trim();
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let value: u64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration '{s}' (expected e.g. 60s, 5m, 2h)"))?;
+    let factor = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        other => return Err(format!("unknown duration unit '{other}' in '{s}'")),
+    };
+    value
+        .checked_mul(factor)
+        .ok_or_else(|| format!("duration '{s}' is too large"))
+}
@@ -1,20 +1,44 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use clap::{Args, Parser, Subcommand, ValueEnum};
-use fs2::FileExt;
 use serde_json::json;
 use std::ffi::OsStr;
-use std::fs::{self, OpenOptions};
+use std::fs;
 use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::scratch::launch_editor_and_get_script;
-
+use rommy::compression::{self, Compression};
+use rommy::parser::{ParseError, Position, RecordReader};
+use rommy::writer::{self, RecordBuilder, RecordLock, RommyCommand};
+
+mod ansi;
+mod clipboard;
+mod compress_cmd;
+mod config;
+mod detach;
+mod diff;
+mod gc;
+mod history;
+mod html;
+mod import;
+mod index;
+mod junit;
+mod limits;
+mod markdown;
+mod migrate;
+mod normalize;
 mod outpath;
+mod progress;
+mod replay;
+mod rerun;
 mod scratch;
+mod signals;
+mod strict;
+mod timing;
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum ColorChoice {
@@ -23,9 +47,33 @@ pub enum ColorChoice {
     Never,
 }
 
+/// What to store for ANSI escape sequences in captured output
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AnsiMode {
+    /// Store output without escape sequences
+    Strip,
+    /// Store output verbatim
+    Keep,
+    /// Store stripped output plus the verbatim output in STDOUT_ANSI/STDERR_ANSI blocks
+    Both,
+}
+
+impl AnsiMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            AnsiMode::Strip => "strip",
+            AnsiMode::Keep => "keep",
+            AnsiMode::Both => "both",
+        }
+    }
+}
+
 const CYAN: &str = "\x1b[36m";
 const YELLOW: &str = "\x1b[33m";
 const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const BOLD: &str = "\x1b[1m";
 
 #[derive(Args, Debug, Clone)]
 pub struct RunConfig {
@@ -41,6 +89,10 @@ pub struct RunConfig {
     #[arg(long = "env", value_name = "KEY=VALUE")]
     pub envs: Vec<String>,
 
+    /// Also store --env values in META (default: only the key names; values may be secrets)
+    #[arg(long)]
+    pub record_env: bool,
+
     /// Append instead of overwrite
     #[arg(long)]
     pub append: bool,
@@ -57,78 +109,771 @@ pub struct RunConfig {
     #[arg(long = "no-stream")]
     pub no_stream: bool,
 
-    /// Command to run (after --). Example: rommy run -- cargo test
-    #[arg(last = true)]
-    pub cmd: Vec<String>,
+    /// With --no-stream: lines of stderr/stdout to print when the command fails (0 = off)
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub tail_lines: usize,
+
+    /// Command to run (after --). Example: rommy run -- cargo test
+    #[arg(last = true)]
+    pub cmd: Vec<String>,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// ANSI escape codes in captured output: strip|keep|both (default: keep)
+    #[arg(long, value_enum, default_value_t = AnsiMode::Keep)]
+    pub ansi: AnsiMode,
+
+    /// Store output exactly as captured (no carriage-return or repeated-line collapsing)
+    #[arg(long = "no-normalize")]
+    pub no_normalize: bool,
+
+    /// Compress automatically named files: zstd|gzip|none (default: `compress` in rommy.conf, else none)
+    #[arg(long, value_name = "CODEC")]
+    pub compress: Option<Compression>,
+
+    /// Limit the command's address space, e.g. 2G (Unix only)
+    #[arg(long, value_name = "SIZE", value_parser = limits::parse_size)]
+    pub limit_mem: Option<u64>,
+
+    /// Limit the command's CPU time, e.g. 60s or 5m (Unix only)
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_secs)]
+    pub limit_cpu: Option<u64>,
+
+    /// Limit the size of files the command writes, e.g. 1G (Unix only)
+    #[arg(long, value_name = "SIZE", value_parser = limits::parse_size)]
+    pub limit_fsize: Option<u64>,
+
+    /// Limit the number of processes of the user, e.g. 256 (Unix only)
+    #[arg(long, value_name = "N")]
+    pub limit_nproc: Option<u64>,
+
+    /// Record when each output chunk arrived, for `rommy replay` and `rommy export asciicast`
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Run in the background; see `rommy status`, `rommy attach`, `rommy stop`
+    #[arg(long)]
+    pub detach: bool,
+
+    /// Copy the record to the clipboard as Markdown (OSC 52) when done
+    #[arg(long, conflicts_with = "detach")]
+    pub copy: bool,
+
+    /// Internal: run as the supervisor of a detached run with this id
+    #[arg(long, hide = true, value_name = "ID")]
+    pub detached_id: Option<String>,
+
+    /// Internal: file to execute for a script record, when it differs from
+    /// the recorded `script_path` (e.g. `rommy rerun`)
+    #[arg(skip)]
+    pub script_file: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "rommy")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub cmd: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    Run {
+        #[command(flatten)]
+        run_config: RunConfig,
+    },
+    Validate {
+        #[command(flatten)]
+        validate_config: ValidateConfig,
+    },
+    Show {
+        #[command(flatten)]
+        show_config: ShowConfig,
+    },
+    /// Copy a record to the clipboard through the terminal (OSC 52)
+    Copy {
+        #[command(flatten)]
+        copy_config: CopyConfig,
+    },
+    /// Convert records for other tools
+    Export {
+        #[command(subcommand)]
+        action: ExportAction,
+    },
+    /// Convert a script(1) typescript, asciinema cast or plain log into a .rommy record
+    Import {
+        #[command(flatten)]
+        import_config: ImportConfig,
+    },
+    /// List records in the output root, newest first
+    Ls {
+        #[command(flatten)]
+        ls_config: LsConfig,
+    },
+    /// Compress plain .rommy files in place (to .rommy.zst or .rommy.gz)
+    Compress {
+        #[command(flatten)]
+        compress_config: CompressConfig,
+    },
+    /// Rewrite older .rommy files in the current format version
+    Migrate {
+        #[command(flatten)]
+        migrate_config: MigrateConfig,
+    },
+    /// Delete old recordings from the output root
+    Gc {
+        #[command(flatten)]
+        gc_config: GcConfig,
+    },
+    /// Maintain the SQLite index of the output root
+    Index {
+        #[command(subcommand)]
+        action: IndexAction,
+    },
+    /// Run SQL against the index, e.g. rommy query "SELECT path, exit_code FROM records"
+    Query {
+        #[command(flatten)]
+        query_config: QueryConfig,
+    },
+    /// Search recorded output with a regular expression (exit status 1 if nothing matched)
+    Search {
+        #[command(flatten)]
+        search_config: SearchConfig,
+    },
+    /// Compare two records (exit status 1 if they differ)
+    Diff {
+        #[command(flatten)]
+        diff_config: DiffConfig,
+    },
+    /// Run a recorded command again and summarise what changed
+    Rerun {
+        #[command(flatten)]
+        rerun_config: RerunConfig,
+    },
+    /// Play a record's output back at the recorded pace (needs `run --timing`)
+    Replay {
+        #[command(flatten)]
+        replay_config: ReplayConfig,
+    },
+    /// List active background runs
+    Status {
+        #[command(flatten)]
+        status_config: StatusConfig,
+    },
+    /// Follow the live output of a background run
+    Attach {
+        #[command(flatten)]
+        attach_config: AttachConfig,
+    },
+    /// Stop a background run and finalise its record
+    Stop {
+        #[command(flatten)]
+        stop_config: StopConfig,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ValidateConfig {
+    /// File(s) or directory path(s) to validate
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Suppress per-file OK lines in text output
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ValidateFormat::Text)]
+    pub format: ValidateFormat,
+
+    /// Also check META semantics: required keys, timestamps, duration, exit code and
+    /// status, duplicate keys, record order. Warnings do not fail validation.
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ValidateFormat {
+    Text,
+    Json,
+    /// One JSON object per file, written as soon as the file is checked
+    Ndjson,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ShowConfig {
+    /// Rommy file to display
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ShowFormat::Text)]
+    pub format: ShowFormat,
+
+    /// Show only one 1-based record index
+    #[arg(long, value_name = "N")]
+    pub record: Option<usize>,
+
+    /// ANSI escape codes in text output: render|strip (default: render if colors are enabled)
+    #[arg(long, value_enum)]
+    pub ansi: Option<ShowAnsi>,
+
+    /// Markdown only: cut long outputs to head/tail to stay within about N tokens
+    #[arg(long, value_name = "TOKENS")]
+    pub budget: Option<usize>,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ShowFormat {
+    Text,
+    Json,
+    /// One JSON object per record, one per line
+    Ndjson,
+    /// Fenced blocks for pasting into a chat
+    Markdown,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ShowAnsi {
+    /// Pass stored escape codes through to the terminal
+    Render,
+    /// Remove escape codes
+    Strip,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CopyConfig {
+    /// Rommy file to copy (default: the most recent record in the output root)
+    #[arg(value_name = "FILE")]
+    pub path: Option<PathBuf>,
+
+    /// Copy only one 1-based record index
+    #[arg(long, value_name = "N")]
+    pub record: Option<usize>,
+
+    /// What to copy
+    #[arg(long, value_enum, default_value_t = CopyFormat::Markdown)]
+    pub format: CopyFormat,
+
+    /// Markdown only: cut long outputs to head/tail to stay within about N tokens
+    #[arg(long, value_name = "TOKENS")]
+    pub budget: Option<usize>,
+
+    /// Write the escape sequence to stdout even if it is not a terminal
+    #[arg(long)]
+    pub print: bool,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum CopyFormat {
+    /// Fenced blocks for pasting into a chat
+    Markdown,
+    /// The records as stored in the .rommy file
+    Raw,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExportAction {
+    /// JUnit XML, one <testcase> per record, for CI test reports
+    Junit {
+        #[command(flatten)]
+        junit_config: JunitConfig,
+    },
+    /// A single self-contained HTML report with an index, filters and colored output
+    Html {
+        #[command(flatten)]
+        html_config: HtmlConfig,
+    },
+    /// An asciicast v2 file of one record (needs `run --timing`)
+    Asciicast {
+        #[command(flatten)]
+        asciicast_config: AsciicastConfig,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AsciicastConfig {
+    /// Rommy file holding the record
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /// 1-based record index (default: last record)
+    #[arg(long, value_name = "N")]
+    pub record: Option<usize>,
+
+    /// Write the cast to FILE instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+
+    /// Terminal width in the cast header
+    #[arg(long, value_name = "N", default_value_t = 80)]
+    pub cols: u32,
+
+    /// Terminal height in the cast header
+    #[arg(long, value_name = "N", default_value_t = 24)]
+    pub rows: u32,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct HtmlConfig {
+    /// File(s) or directory path(s) to export
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Write the report to FILE instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+
+    /// Page title (default: "rommy report")
+    #[arg(long)]
+    pub title: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct JunitConfig {
+    /// File(s) or directory path(s) to export
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Write the XML to FILE instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+
+    /// One <testsuite> per file or per label
+    #[arg(long, value_enum, default_value_t = JunitGroup::File)]
+    pub group_by: JunitGroup,
+
+    /// Lines of stderr to put into <failure>
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub tail_lines: usize,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum JunitGroup {
+    File,
+    Label,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImportConfig {
+    /// Recording to import
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /// Kind of recording
+    #[arg(long, value_enum)]
+    pub from: ImportFrom,
+
+    /// Timing file written by `script -t` (or `script -T`), for the duration
+    #[arg(long, value_name = "FILE")]
+    pub timing: Option<PathBuf>,
+
+    /// Output file (optional; if omitted, Rommy chooses a path from the recording's time)
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+
+    /// Append to an existing --out file
+    #[arg(long, requires = "out")]
+    pub append: bool,
+
+    /// Overwrite an existing --out file
+    #[arg(long, requires = "out", conflicts_with = "append")]
+    pub force: bool,
+
+    /// Optional label to include in META
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// ANSI escape codes in the imported output: strip|keep|both (default: keep)
+    #[arg(long, value_enum, default_value_t = AnsiMode::Keep)]
+    pub ansi: AnsiMode,
+
+    /// Store output exactly as recorded (no carriage-return or repeated-line collapsing)
+    #[arg(long = "no-normalize")]
+    pub no_normalize: bool,
+
+    /// Compress automatically named files: zstd|gzip|none (default: `compress` in rommy.conf, else none)
+    #[arg(long, value_name = "CODEC")]
+    pub compress: Option<Compression>,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ImportFrom {
+    /// Typescript written by script(1)
+    Script,
+    /// asciinema recording (.cast, versions 1 to 3)
+    Asciicast,
+    /// Plain text log
+    Log,
+}
+
+impl ImportFrom {
+    fn as_str(self) -> &'static str {
+        match self {
+            ImportFrom::Script => "script",
+            ImportFrom::Asciicast => "asciicast",
+            ImportFrom::Log => "log",
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct LsConfig {
+    /// Directory or file to list (default: the output root, see ROMMY_ROOT)
+    #[arg(value_name = "PATH")]
+    pub path: Option<PathBuf>,
+
+    /// Only records started at or after this time: 2d, 12h, 2025-10-26, 2025-10-26T17:30
+    #[arg(long, value_name = "TIME", value_parser = history::parse_time_bound)]
+    pub since: Option<DateTime<Utc>>,
+
+    /// Only records started at or before this time (same forms as --since)
+    #[arg(long, value_name = "TIME", value_parser = history::parse_time_bound)]
+    pub until: Option<DateTime<Utc>>,
+
+    /// Only records with this label
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// Only records with this status: ok, error, killed, limit_mem, ...
+    #[arg(long)]
+    pub status: Option<String>,
+
+    /// Only records run in DIR or below
+    #[arg(long, value_name = "DIR")]
+    pub cwd: Option<PathBuf>,
+
+    /// Only records whose COMMAND matches this regular expression
+    #[arg(long, value_name = "PATTERN")]
+    pub grep_command: Option<String>,
+
+    /// Show at most N records
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = LsFormat::Text)]
+    pub format: LsFormat,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum LsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MigrateConfig {
+    /// File(s) or directory path(s) to migrate
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Print a diff of what would change without touching any file
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CompressConfig {
+    /// File(s) or directory path(s) to compress
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// zstd or gzip
+    #[arg(long, value_name = "CODEC", default_value = "zstd")]
+    pub codec: Compression,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GcConfig {
+    /// Delete files whose newest record is older than this: 30d, 12h, ...
+    #[arg(long, value_name = "DURATION", value_parser = limits::parse_secs)]
+    pub older_than: Option<u64>,
+
+    /// Delete the oldest files until the store is at most this size: 5G, 500M, ...
+    #[arg(long, value_name = "SIZE", value_parser = limits::parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Keep files containing a record whose status is not ok
+    #[arg(long)]
+    pub keep_failed: bool,
+
+    /// Keep files containing a labeled record
+    #[arg(long)]
+    pub keep_labeled: bool,
+
+    /// Keep the newest N files per command
+    #[arg(long, value_name = "N")]
+    pub keep_last: Option<usize>,
+
+    /// Only report what would be deleted
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum IndexAction {
+    /// Recreate <root>/index.sqlite from all files in the output root
+    Rebuild,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct QueryConfig {
+    /// SQL statement; tables: files, records, output (FTS5 over command/stdout/stderr)
+    #[arg(value_name = "SQL")]
+    pub sql: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = QueryFormat::Text)]
+    pub format: QueryFormat,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum QueryFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SearchConfig {
+    /// Regular expression to search for
+    #[arg(value_name = "REGEX")]
+    pub pattern: String,
+
+    /// Files or directories to search (default: the output root, see ROMMY_ROOT)
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+
+    /// Blocks to search (comma-separated)
+    #[arg(
+        long = "in",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "command,stdout,stderr"
+    )]
+    pub blocks: Vec<SearchBlock>,
+
+    /// Lines of context before and after each match
+    #[arg(short = 'C', long, value_name = "N", default_value_t = 0)]
+    pub context: usize,
+
+    /// Match case-insensitively
+    #[arg(short = 'i', long)]
+    pub ignore_case: bool,
+
+    /// Stop after N matches
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = SearchFormat::Text)]
+    pub format: SearchFormat,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SearchBlock {
+    Command,
+    Stdout,
+    Stderr,
+    Meta,
+}
+
+impl SearchBlock {
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchBlock::Command => "command",
+            SearchBlock::Stdout => "stdout",
+            SearchBlock::Stderr => "stderr",
+            SearchBlock::Meta => "meta",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum SearchFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DiffConfig {
+    /// First record: FILE or FILE#N (default: last record in FILE)
+    #[arg(value_name = "A")]
+    pub a: String,
+
+    /// Second record: FILE or FILE#N (default: last record in FILE)
+    #[arg(value_name = "B")]
+    pub b: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+    pub format: DiffFormat,
+
+    /// Mask noise in STDOUT/STDERR before comparing (comma-separated)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "timestamps,temp-paths,durations"
+    )]
+    pub normalize: Vec<diff::Normalizer>,
+
+    /// Compare output exactly as recorded
+    #[arg(long = "no-normalize", conflicts_with = "normalize")]
+    pub no_normalize: bool,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+    SideBySide,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RerunConfig {
+    /// Rommy file holding the record to rerun
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /// 1-based record index (default: last record)
+    #[arg(long, value_name = "N")]
+    pub record: Option<usize>,
+
+    /// Output file (optional; if omitted, Rommy chooses a time-based path)
+    #[arg(long, value_name = "FILE")]
+    pub out: Option<PathBuf>,
+
+    /// Append instead of overwrite
+    #[arg(long)]
+    pub append: bool,
+
+    /// Label for the new record (default: the original label)
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// Disable live streaming to terminal (default: streaming ON)
+    #[arg(long = "no-stream")]
+    pub no_stream: bool,
+
+    /// With --no-stream: lines of stderr/stdout to print when the command fails (0 = off)
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub tail_lines: usize,
+
+    /// Store output exactly as captured (no carriage-return or repeated-line collapsing)
+    #[arg(long = "no-normalize")]
+    pub no_normalize: bool,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ReplayConfig {
+    /// Rommy file holding the record
+    #[arg(value_name = "FILE")]
+    pub path: PathBuf,
+
+    /// 1-based record index (default: last record)
+    #[arg(long, value_name = "N")]
+    pub record: Option<usize>,
+
+    /// Playback speed, e.g. 2x or 0.5x
+    #[arg(long, value_name = "FACTOR", default_value = "1x", value_parser = replay::parse_speed)]
+    pub speed: f64,
 
     /// Color output: auto|always|never (default: auto)
     #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
     pub color: ColorChoice,
 }
 
-#[derive(Parser, Debug)]
-#[command(name = "rommy")]
-pub struct Cli {
-    #[command(subcommand)]
-    pub cmd: Commands,
-}
-
-#[derive(Subcommand, Debug)]
-pub enum Commands {
-    Run {
-        #[command(flatten)]
-        run_config: RunConfig,
-    },
-    Validate {
-        #[command(flatten)]
-        validate_config: ValidateConfig,
-    },
-    Show {
-        #[command(flatten)]
-        show_config: ShowConfig,
-    },
-}
-
 #[derive(Args, Debug, Clone)]
-pub struct ValidateConfig {
-    /// File(s) or directory path(s) to validate
-    #[arg(value_name = "PATH", required = true)]
-    pub paths: Vec<PathBuf>,
-
-    /// Suppress per-file OK lines in text output
-    #[arg(long)]
-    pub quiet: bool,
-
+pub struct StatusConfig {
     /// Output format
-    #[arg(long, value_enum, default_value_t = ValidateFormat::Text)]
-    pub format: ValidateFormat,
+    #[arg(long, value_enum, default_value_t = StatusFormat::Text)]
+    pub format: StatusFormat,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
-pub enum ValidateFormat {
+pub enum StatusFormat {
     Text,
     Json,
 }
 
 #[derive(Args, Debug, Clone)]
-pub struct ShowConfig {
-    /// Rommy file to display
-    #[arg(value_name = "FILE")]
-    pub path: PathBuf,
+pub struct AttachConfig {
+    /// Run id (or unique prefix) as printed by `rommy run --detach`
+    #[arg(value_name = "ID")]
+    pub id: String,
 
-    /// Output format
-    #[arg(long, value_enum, default_value_t = ShowFormat::Text)]
-    pub format: ShowFormat,
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+}
 
-    /// Show only one 1-based record index
-    #[arg(long, value_name = "N")]
-    pub record: Option<usize>,
+#[derive(Args, Debug, Clone)]
+pub struct StopConfig {
+    /// Run id (or unique prefix) as printed by `rommy run --detach`
+    #[arg(value_name = "ID")]
+    pub id: String,
+
+    /// Signal sent to the command's process group
+    #[arg(long, value_enum, default_value_t = StopSignal::Term)]
+    pub signal: StopSignal,
+
+    /// Seconds to wait for the record to be finalised
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub timeout: u64,
+
+    /// Color output: auto|always|never (default: auto)
+    #[arg(long = "color", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
-pub enum ShowFormat {
-    Text,
-    Json,
+pub enum StopSignal {
+    Term,
+    Int,
+    Kill,
 }
 
 fn main() -> Result<()> {
@@ -137,22 +882,70 @@ fn main() -> Result<()> {
         Commands::Run { run_config } => run(run_config),
         Commands::Validate { validate_config } => validate(validate_config),
         Commands::Show { show_config } => show(show_config),
+        Commands::Copy { copy_config } => clipboard::copy(copy_config),
+        Commands::Export {
+            action: ExportAction::Junit { junit_config },
+        } => junit::export(junit_config),
+        Commands::Export {
+            action: ExportAction::Html { html_config },
+        } => html::export(html_config),
+        Commands::Export {
+            action: ExportAction::Asciicast { asciicast_config },
+        } => replay::export_asciicast(asciicast_config),
+        Commands::Import { import_config } => import::import(import_config),
+        Commands::Ls { ls_config } => history::ls(ls_config),
+        Commands::Compress { compress_config } => compress_cmd::compress(compress_config),
+        Commands::Migrate { migrate_config } => migrate::migrate(migrate_config),
+        Commands::Gc { gc_config } => gc::gc(gc_config),
+        Commands::Index {
+            action: IndexAction::Rebuild,
+        } => index::rebuild(),
+        Commands::Query { query_config } => index::query(query_config),
+        Commands::Search { search_config } => match history::search(search_config) {
+            Ok(true) => Ok(()),
+            Ok(false) => std::process::exit(1),
+            // Like grep(1): 1 means "no match", trouble is 2
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                std::process::exit(2)
+            }
+        },
+        Commands::Diff { diff_config } => match diff::diff(diff_config) {
+            Ok(false) => Ok(()),
+            Ok(true) => std::process::exit(1),
+            // Like diff(1): 1 means "different", trouble is 2
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                std::process::exit(2)
+            }
+        },
+        Commands::Rerun { rerun_config } => rerun::rerun(rerun_config),
+        Commands::Replay { replay_config } => replay::replay(replay_config),
+        Commands::Status { status_config } => detach::status(status_config),
+        Commands::Attach { attach_config } => detach::attach(attach_config),
+        Commands::Stop { stop_config } => detach::stop(stop_config),
     }
 }
 
 /// Führt den Child-Prozess aus.
+/// - log=Some: stdout/stderr werden in das Live-Log eines Hintergrundlaufs geschrieben UND gesammelt.
 /// - stream=true: stdout/stderr werden live ins Terminal gespiegelt UND gesammelt.
-/// - stream=false: stdout/stderr werden vollständig gesammelt (keine Terminalausgabe).
-///   Rückgabe: (stdout_bytes, stderr_bytes, exit_code)
+/// - stream=false: stdout/stderr werden vollständig gesammelt (keine Terminalausgabe);
+///   ist stderr ein Terminal, zeigt eine einzelne Statuszeile den Fortschritt.
+/// - timing=Some: Zeitpunkt und Größe jedes gelesenen Chunks werden mitprotokolliert.
+///   Rückgabe: (stdout_bytes, stderr_bytes, exit_status)
 fn spawn_and_stream(
     mut child: Child,
     stream: bool,
     colors: bool,
-) -> anyhow::Result<(Vec<u8>, Vec<u8>, i32)> {
-    fn tee<R: Read + Send + 'static, W: Write + Send + 'static>(
+    log: Option<fs::File>,
+    timing: Option<Arc<timing::ChunkLog>>,
+) -> anyhow::Result<(Vec<u8>, Vec<u8>, ExitStatus)> {
+    fn tee<R: Read + Send + 'static>(
         mut r: R,
-        mut w: W,
+        mut w: Box<dyn Write + Send>,
         colorize_each_chunk: bool,
+        timing: Option<(Arc<timing::ChunkLog>, bool)>,
     ) -> thread::JoinHandle<Vec<u8>> {
         thread::spawn(move || {
             let mut buf = [0u8; 8192];
@@ -161,6 +954,9 @@ fn spawn_and_stream(
                 match r.read(&mut buf) {
                     Ok(0) => break,
                     Ok(n) => {
+                        if let Some((log, is_stderr)) = &timing {
+                            log.record(*is_stderr, n);
+                        }
                         if colorize_each_chunk {
                             let _ = w.write_all(YELLOW.as_bytes());
                             let _ = w.write_all(&buf[..n]);
@@ -178,146 +974,67 @@ fn spawn_and_stream(
         })
     }
 
-    if stream {
-        // take() nur im Streaming-Zweig
-        let out_r = child.stdout.take();
-        let err_r = child.stderr.take();
+    type Writers = (Box<dyn Write + Send>, Box<dyn Write + Send>, bool);
+    let writers: Option<Writers> = match log {
+        Some(log) => Some((Box::new(log.try_clone()?), Box::new(log), false)),
+        None if stream => Some((Box::new(io::stdout()), Box::new(io::stderr()), colors)),
+        None if io::stderr().is_terminal() => None,
+        // Kein Streaming, aber Timing: Chunks einzeln lesen, Ausgabe verwerfen
+        None if timing.is_some() => Some((Box::new(io::sink()), Box::new(io::sink()), false)),
+        None => {
+            // kein Streaming: keine take(); vollständiges Lesen
+            let output = child.wait_with_output()?;
+            return Ok((output.stdout, output.stderr, output.status));
+        }
+    };
 
-        // stdout: niemals einfärben
-        let h_out = out_r.map(|r| tee(r, io::stdout(), false));
-        // stderr: pro Chunk einfärben (nur wenn colors=true)
-        let h_err = err_r.map(|r| tee(r, io::stderr(), colors));
+    let Some((out_w, err_w, colors)) = writers else {
+        return progress::capture_with_status_line(child, colors, timing);
+    };
 
-        let status = child.wait()?;
-        let code = status.code().unwrap_or(-1);
+    // take() nur im Streaming-Zweig
+    let out_r = child.stdout.take();
+    let err_r = child.stderr.take();
 
-        let stdout_bytes = h_out
-            .map(|h| h.join().unwrap_or_default())
-            .unwrap_or_default();
-        let stderr_bytes = h_err
-            .map(|h| h.join().unwrap_or_default())
-            .unwrap_or_default();
+    // stdout: niemals einfärben
+    let h_out = out_r.map(|r| tee(r, out_w, false, timing.clone().map(|t| (t, false))));
+    // stderr: pro Chunk einfärben (nur wenn colors=true)
+    let h_err = err_r.map(|r| tee(r, err_w, colors, timing.map(|t| (t, true))));
 
-        Ok((stdout_bytes, stderr_bytes, code))
-    } else {
-        // kein Streaming: keine take(); vollständiges Lesen
-        let output = child.wait_with_output()?;
-        let code = output.status.code().unwrap_or(-1);
-        Ok((output.stdout, output.stderr, code))
-    }
-}
-
-fn temp_out_path(out_path: &Path) -> PathBuf {
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    let pid = std::process::id();
-    let base = out_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("rommy.rommy");
-    let mut tmp = out_path.to_path_buf();
-    tmp.set_file_name(format!(".{base}.{pid}.{nanos}.tmp"));
-    tmp
-}
-
-fn lock_path(out_path: &Path) -> PathBuf {
-    let mut lock = out_path.to_path_buf();
-    let base = out_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("rommy.rommy");
-    lock.set_file_name(format!(".{base}.lock"));
-    lock
-}
-
-struct RecordData<'a> {
-    rommy_version: &'a str,
-    label: Option<&'a str>,
-    cwd_abs: &'a Path,
-    user: Option<&'a str>,
-    host: Option<&'a str>,
-    display_command: &'a RommyCommand,
-    start: &'a DateTime<Utc>,
-    end: &'a DateTime<Utc>,
-    duration_ms: i64,
-    out_path: &'a Path,
-    status_str: &'a str,
-    exit_code: i32,
-    stdout_bytes: &'a [u8],
-    stderr_bytes: &'a [u8],
+    let status = child.wait()?;
+
+    let stdout_bytes = h_out
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
+    let stderr_bytes = h_err
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
+
+    Ok((stdout_bytes, stderr_bytes, status))
 }
 
-fn write_record(f: &mut fs::File, data: &RecordData<'_>) -> Result<()> {
-    // META
-    writeln!(f, "<<<META>>>")?;
-    writeln!(f, "rommy_version: {}", data.rommy_version)?;
-    if let Some(label) = data.label {
-        writeln!(f, "label: {}", label)?;
-    }
-    writeln!(f, "cwd: {}", data.cwd_abs.display())?;
-    if let Some(user) = data.user {
-        writeln!(f, "user: {}", user)?;
-    }
-    if let Some(host) = data.host {
-        writeln!(f, "host: {}", host)?;
-    }
-    match data.display_command {
-        RommyCommand::Script { path, .. } => {
-            writeln!(f, "script_path: {}", path.display())?;
-        }
-        RommyCommand::Line(line) => {
-            writeln!(f, "command_line: {}", line)?;
-        }
+fn run(cfg: RunConfig) -> Result<()> {
+    if cfg.detach {
+        return detach::start(cfg);
     }
-    writeln!(f, "start_ts: {}", data.start.to_rfc3339())?;
-    writeln!(f, "end_ts: {}", data.end.to_rfc3339())?;
-    writeln!(f, "duration_ms: {}", data.duration_ms)?;
-    writeln!(f, "output_path: {}", data.out_path.display())?;
-    writeln!(f, "status: {}", data.status_str)?;
-    writeln!(f, "exit_code: {}", data.exit_code)?;
-    writeln!(f, "<<<END>>>")?;
-
-    // COMMAND
-    writeln!(f, "<<<COMMAND>>>")?;
-    match data.display_command {
-        RommyCommand::Script { content, .. } => {
-            f.write_all(content.as_bytes())?;
-        }
-        RommyCommand::Line(line) => {
-            writeln!(f, "$ {}", line)?;
+    let display_command = resolve_command(&cfg)?;
+    let copy = cfg.copy;
+    let colors = color_is_enabled(cfg.color);
+    let outcome = execute(cfg, display_command, Vec::new())?;
+    // The record is already written; a terminal without a clipboard must not fail the run
+    if copy {
+        match clipboard::copy_last_record(&outcome.out_path) {
+            Ok(()) => rommy_note_cyan(colors, "Copied the record as Markdown"),
+            Err(err) => rommy_note_cyan(colors, &format!("Not copied: {err:#}")),
         }
     }
-    writeln!(f, "<<<END>>>")?;
-
-    // STDOUT
-    writeln!(f, "<<<STDOUT>>>")?;
-    f.write_all(data.stdout_bytes)?;
-    if !data.stdout_bytes.is_empty() && !data.stdout_bytes.ends_with(b"\n") {
-        writeln!(f)?;
-    }
-    writeln!(f, "<<<END>>>")?;
-
-    // STDERR
-    writeln!(f, "<<<STDERR>>>")?;
-    f.write_all(data.stderr_bytes)?;
-    if !data.stderr_bytes.is_empty() && !data.stderr_bytes.ends_with(b"\n") {
-        writeln!(f)?;
-    }
-    writeln!(f, "<<<END>>>")?;
-
     Ok(())
 }
 
-fn run(cfg: RunConfig) -> Result<()> {
-    let stream = !cfg.no_stream;
-    let colors = color_is_enabled(cfg.color);
-    // Resolve CWD
-    let cwd_path = cfg.cwd.unwrap_or(std::env::current_dir()?);
-
+/// Work out what to run: a script file, a scratch script from the editor, or the command after --.
+fn resolve_command(cfg: &RunConfig) -> Result<RommyCommand> {
     let script = if cfg.script.is_some() {
-        cfg.script
+        cfg.script.clone()
     } else if cfg.cmd.is_empty() {
         let script = launch_editor_and_get_script()?;
         Some(script)
@@ -325,55 +1042,150 @@ fn run(cfg: RunConfig) -> Result<()> {
         None
     };
 
-    // Build command invocation
-    let (display_command, exec) = if let Some(script_path) = &script {
+    if let Some(script_path) = &script {
         let script_abs = fs::canonicalize(script_path)
             .with_context(|| format!("Cannot resolve script path: {}", script_path.display()))?;
         let script_text = fs::read_to_string(&script_abs)
             .with_context(|| format!("Cannot read script: {}", script_abs.display()))?;
 
         let display = format!("#!/usr/bin/env bash\n{}\n", script_text);
-
-        // Execute bash with -Eeuo pipefail for safety & clear failures
-        let mut command = Command::new("bash");
-        command
-            .arg("-Eeuo")
-            .arg("pipefail")
-            .arg(&script_abs)
-            .current_dir(&cwd_path)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        (
-            RommyCommand::Script {
-                path: script_abs,
-                content: display,
-            },
-            command,
-        )
+        Ok(RommyCommand::Script {
+            path: script_abs,
+            content: display,
+        })
     } else {
         anyhow::ensure!(
             !cfg.cmd.is_empty(),
             "Provide either --script <file> or a command after --"
         );
-        let bash_line = shell_join(&cfg.cmd)?;
-        let mut command = Command::new("bash");
-        command
-            .arg("-lc")
-            .arg(&bash_line)
-            .current_dir(&cwd_path)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        (RommyCommand::Line(bash_line), command)
+        Ok(RommyCommand::Line(shell_join(&cfg.cmd)?))
+    }
+}
+
+/// What `execute` recorded, for callers that want to report on it (e.g. `rommy rerun`).
+struct RunOutcome {
+    out_path: PathBuf,
+    exit_code: i32,
+    duration_ms: i64,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Time-based path under the output root, with the suffix of the configured compression.
+/// An explicit --out keeps its name; its `.zst`/`.gz` suffix decides the compression.
+fn auto_out_path(
+    compress: Option<Compression>,
+    token: &str,
+    when: DateTime<Local>,
+) -> Result<PathBuf> {
+    let compression = match compress {
+        Some(c) => c,
+        None => match config::load()?.get("compress") {
+            Some(value) => value
+                .parse::<Compression>()
+                .map_err(|e| anyhow::anyhow!("compress: {e}"))?,
+            None => Compression::None,
+        },
+    };
+    let path = outpath::resolve_auto_out_path(token, when)
+        .context("failed to resolve automatic output path")?;
+    Ok(compression::with_suffix(&path, compression))
+}
+
+/// HEAD commit of the git repository containing `dir`, if any. Reads `.git`
+/// directly rather than spawning git on every run.
+fn git_commit(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|d| d.join(".git"))
+        .find(|p| p.exists())?;
+    // Worktrees and submodules have a `.git` file pointing at the real directory
+    let git_dir = if dot_git.is_file() {
+        let text = fs::read_to_string(&dot_git).ok()?;
+        let target = Path::new(text.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    // Branches live in the main repository's directory, named by `commondir`
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(rel) => git_dir.join(rel.trim()),
+        Err(_) => git_dir.clone(),
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let Some(reference) = head.trim().strip_prefix("ref:").map(str::trim) else {
+        // Detached HEAD
+        return is_object_id(head.trim()).then(|| head.trim().to_string());
+    };
+    let commit = [&git_dir, &common_dir]
+        .into_iter()
+        .find_map(|d| fs::read_to_string(d.join(reference)).ok())
+        .map(|id| id.trim().to_string())
+        .or_else(|| {
+            let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+            packed.lines().find_map(|line| {
+                let (id, name) = line.split_once(' ')?;
+                (name == reference).then(|| id.to_string())
+            })
+        })?;
+    is_object_id(&commit).then_some(commit)
+}
+
+fn is_object_id(s: &str) -> bool {
+    matches!(s.len(), 40 | 64) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Run the command and write its record. `extra_meta` is added to the record's META.
+fn execute(
+    cfg: RunConfig,
+    display_command: RommyCommand,
+    mut extra_meta: Vec<(String, String)>,
+) -> Result<RunOutcome> {
+    let stream = !cfg.no_stream;
+    let colors = color_is_enabled(cfg.color);
+    // Resolve CWD
+    let cwd_path = cfg.cwd.unwrap_or(std::env::current_dir()?);
+
+    // Build command invocation
+    let exec = match &display_command {
+        RommyCommand::Script { path, .. } => {
+            // Execute bash with -Eeuo pipefail for safety & clear failures
+            let mut command = Command::new("bash");
+            command
+                .arg("-Eeuo")
+                .arg("pipefail")
+                .arg(cfg.script_file.as_deref().unwrap_or(path))
+                .current_dir(&cwd_path)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            command
+        }
+        RommyCommand::Line(bash_line) => {
+            let mut command = Command::new("bash");
+            command
+                .arg("-lc")
+                .arg(bash_line)
+                .current_dir(&cwd_path)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            command
+        }
     };
 
     // Apply envs
     let mut command = exec;
-    for kv in cfg.envs {
+    let mut env_keys = Vec::new();
+    for kv in &cfg.envs {
         if let Some((k, v)) = kv.split_once('=') {
             command.env(k, v);
+            // Key names tell `rommy rerun` what to pass on; values only on request
+            env_keys.push(k.to_string());
+            if cfg.record_env {
+                extra_meta.push((format!("env.{k}"), v.to_string()));
+            }
         } else {
             eprintln!(
                 "WARN: ignoring malformed --env '{}', expected KEY=VALUE",
@@ -382,24 +1194,108 @@ fn run(cfg: RunConfig) -> Result<()> {
         }
     }
 
+    if !env_keys.is_empty() {
+        extra_meta.push(("env_keys".to_string(), env_keys.join(", ")));
+    }
+
     // Collect metadata
-    let rommy_version = env!("CARGO_PKG_VERSION").to_string();
     let user = whoami::username().ok();
     let host = whoami::hostname().ok();
+    if let Some(commit) = git_commit(&cwd_path) {
+        extra_meta.push(("git_commit".to_string(), commit));
+    }
 
     // Ensure output pipes are always configured for both streaming and capture mode.
     command.stdout(Stdio::piped()).stderr(Stdio::piped());
 
+    let limits = limits::Limits {
+        mem: cfg.limit_mem,
+        cpu: cfg.limit_cpu,
+        fsize: cfg.limit_fsize,
+        nproc: cfg.limit_nproc,
+    };
+    limits.apply(&mut command)?;
+
+    // A detached run gets its own process group, so `rommy stop` can signal
+    // the whole command tree without hitting the supervisor.
+    #[cfg(unix)]
+    if cfg.detached_id.is_some() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    // Supervisor of a detached run: register it and tee output into the live log.
+    // The registration is dropped (and the run unregistered) once the record is written.
+    let (mut registration, log) = match &cfg.detached_id {
+        Some(id) => {
+            let out = cfg.out.as_deref().context("detached run without --out")?;
+            let (registration, log) =
+                detach::Registration::create(id, out, &display_command.summary())?;
+            (Some(registration), Some(log))
+        }
+        None => (None, None),
+    };
+
     let start: DateTime<Utc> = Utc::now();
-    let child = command.spawn().with_context(|| "Failed to spawn process")?;
+    let chunk_log = cfg.timing.then(|| Arc::new(timing::ChunkLog::new()));
+    let mut child = command.spawn().with_context(|| "Failed to spawn process")?;
+    if let Some(registration) = registration.as_mut() {
+        registration.started(&mut child)?;
+    }
 
-    let (stdout_bytes, stderr_bytes, exit_code) =
-        spawn_and_stream(child, stream, colors).with_context(|| "stream/capture failed")?;
+    let (raw_stdout, raw_stderr, exit_status) =
+        spawn_and_stream(child, stream, colors, log, chunk_log.clone())
+            .with_context(|| "stream/capture failed")?;
 
     let end: DateTime<Utc> = Utc::now();
     let duration_ms = (end - start).num_milliseconds();
 
-    let status_str = if exit_code == 0 { "ok" } else { "error" };
+    let exit_code = exit_status.code().unwrap_or(-1);
+    let signal = signals::exit_signal(&exit_status);
+    extra_meta.extend(limits.meta());
+    if let Some(sig) = signal {
+        extra_meta.push(("signal".to_string(), signals::signal_name(sig)));
+    }
+    let limit_hit = if limits.is_empty() {
+        None
+    } else {
+        limits.exceeded(exit_code, signal, limits::children_cpu_secs(), &raw_stderr)
+    };
+    let status_str = if exit_code == 0 {
+        "ok"
+    } else if let Some(reason) = limit_hit {
+        reason
+    } else if signal.is_some() {
+        "killed"
+    } else {
+        "error"
+    };
+
+    // Chunk sizes are measured on the raw capture; keep it to relate them to what is stored
+    let raw_for_timing = chunk_log
+        .as_ref()
+        .map(|_| (raw_stdout.clone(), raw_stderr.clone()));
+
+    // Normalising and ANSI handling only affect what is stored;
+    // the live stream above stays untouched.
+    let (raw_stdout, raw_stderr) = if cfg.no_normalize {
+        (raw_stdout, raw_stderr)
+    } else {
+        (
+            normalize_stream("stdout", &raw_stdout, &mut extra_meta),
+            normalize_stream("stderr", &raw_stderr, &mut extra_meta),
+        )
+    };
+    let (stdout_bytes, stdout_ansi) = apply_ansi_mode(cfg.ansi, raw_stdout);
+    let (stderr_bytes, stderr_ansi) = apply_ansi_mode(cfg.ansi, raw_stderr);
+    if cfg.ansi != AnsiMode::Keep {
+        extra_meta.push(("ansi".to_string(), cfg.ansi.as_str().to_string()));
+    }
+
+    // Nothing was shown during a --no-stream run, so surface the end of a failure
+    if !stream && cfg.detached_id.is_none() && exit_code != 0 {
+        progress::print_failure_tail(&stdout_bytes, &stderr_bytes, cfg.tail_lines, colors);
+    }
 
     // Bestimme Ausgabedatei
     let out_path: PathBuf = if let Some(explicit) = cfg.out {
@@ -410,100 +1306,122 @@ fn run(cfg: RunConfig) -> Result<()> {
             RommyCommand::Script { .. } => "#!/usr/bin/env bash\n<script>".to_string(),
             RommyCommand::Line(line) => format!("$ {}", line),
         };
-        outpath::resolve_auto_out_path(&display_for_token)
-            .context("failed to resolve automatic output path")?
+        auto_out_path(cfg.compress, &display_for_token, Local::now())?
     };
 
     // Prepare writer
-    if let Some(parent) = out_path.parent()
-        && !parent.exists()
-    {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Cannot create directory {}", parent.display()))?;
-    }
-    let lock_path = lock_path(&out_path);
-    let lock_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(false)
-        .open(&lock_path)
-        .with_context(|| format!("Cannot open lock file {}", lock_path.display()))?;
-    lock_file
-        .lock_exclusive()
-        .with_context(|| format!("Cannot acquire lock {}", lock_path.display()))?;
+    writer::create_parent_dir(&out_path)?;
+    let lock = RecordLock::acquire(&out_path)?;
 
     let cwd_abs = fs::canonicalize(&cwd_path)
         .with_context(|| format!("Cannot resolve cwd {}", cwd_path.display()))?;
-    let label = cfg.label.as_deref();
-    let record_data = RecordData {
-        rommy_version: &rommy_version,
-        label,
-        cwd_abs: &cwd_abs,
-        user: user.as_deref(),
-        host: host.as_deref(),
-        display_command: &display_command,
-        start: &start,
-        end: &end,
-        duration_ms,
-        out_path: &out_path,
-        status_str,
-        exit_code,
-        stdout_bytes: &stdout_bytes,
-        stderr_bytes: &stderr_bytes,
-    };
-
-    let tmp_path = temp_out_path(&out_path);
-    let write_result = (|| -> Result<()> {
-        let mut temp = OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(&tmp_path)
-            .with_context(|| format!("Cannot create {}", tmp_path.display()))?;
-
-        if cfg.append {
-            // Best effort atomic append: copy old file into temp first, then append new record.
-            match fs::File::open(&out_path) {
-                Ok(mut current) => {
-                    io::copy(&mut current, &mut temp).with_context(|| {
-                        format!("Cannot copy existing content from {}", out_path.display())
-                    })?;
-                }
-                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
-                Err(e) => {
-                    return Err(e).with_context(|| format!("Cannot open {}", out_path.display()));
-                }
-            }
-        }
-
-        write_record(&mut temp, &record_data)?;
+    let timing_block = chunk_log
+        .zip(raw_for_timing)
+        .map(|(log, (raw_out, raw_err))| {
+            log.to_block([&raw_out, &raw_err], [&stdout_bytes, &stderr_bytes])
+        });
+    let mut builder = RecordBuilder::new(&display_command);
+    if let Some(label) = &cfg.label {
+        builder = builder.label(label);
+    }
+    builder = builder.cwd(&cwd_abs);
+    if let Some(user) = user {
+        builder = builder.user(user);
+    }
+    if let Some(host) = host {
+        builder = builder.host(host);
+    }
+    builder = builder.times(start, end).output_path(&out_path);
+    for (key, value) in extra_meta {
+        builder = builder.meta(key, value);
+    }
+    builder = builder
+        .exit(exit_code, status_str)
+        // Records are text: invalid UTF-8 becomes U+FFFD (see writer::write_record)
+        .stdout(String::from_utf8_lossy(&stdout_bytes))
+        .stderr(String::from_utf8_lossy(&stderr_bytes));
+    if let Some(bytes) = &stdout_ansi {
+        builder = builder.stdout_ansi(String::from_utf8_lossy(bytes));
+    }
+    if let Some(bytes) = &stderr_ansi {
+        builder = builder.stderr_ansi(String::from_utf8_lossy(bytes));
+    }
+    if let Some(timing) = timing_block {
+        builder = builder.timing(timing);
+    }
 
-        temp.sync_all()
-            .with_context(|| format!("Cannot sync {}", tmp_path.display()))?;
+    let mut record = Vec::new();
+    writer::write_record(&mut record, &builder.build())?;
+    writer::replace_file(
+        &out_path,
+        &Compression::from_path(&out_path).encode(&record)?,
+        cfg.append,
+        &lock,
+    )?;
+    // Still under the lock, so concurrent appends reach the index in order
+    if let Err(err) = index::update_after_write(&out_path) {
+        rommy_note_cyan(colors, &format!("Index not updated: {err:#}"));
+    }
+    drop(lock);
+    drop(registration);
 
-        #[cfg(windows)]
-        if out_path.exists() {
-            fs::remove_file(&out_path)
-                .with_context(|| format!("Cannot replace {}", out_path.display()))?;
-        }
+    rommy_note_cyan(colors, &format!("Wrote {}", out_path.display()));
 
-        fs::rename(&tmp_path, &out_path).with_context(|| {
-            format!(
-                "Cannot atomically move {} to {}",
-                tmp_path.display(),
-                out_path.display()
-            )
-        })?;
-        Ok(())
-    })();
+    // Retention policy from rommy.conf (gc.auto); a failure here must not fail the run
+    match gc::auto(&out_path) {
+        Ok(Some((files, bytes))) if files > 0 => rommy_note_cyan(
+            colors,
+            &format!(
+                "gc: removed {} old file(s), {}",
+                files,
+                progress::format_bytes(bytes as usize)
+            ),
+        ),
+        Ok(_) => {}
+        Err(err) => rommy_note_cyan(colors, &format!("gc skipped: {err:#}")),
+    }
+    Ok(RunOutcome {
+        out_path,
+        exit_code,
+        duration_ms,
+        stdout: stdout_bytes,
+        stderr: stderr_bytes,
+    })
+}
 
-    if let Err(err) = write_result {
-        let _ = fs::remove_file(&tmp_path);
-        return Err(err);
+/// Normalise one captured stream, noting what was removed in `extra_meta`.
+fn normalize_stream(name: &str, raw: &[u8], extra_meta: &mut Vec<(String, String)>) -> Vec<u8> {
+    let (out, stats) = normalize::normalize(raw);
+    if stats.removed_bytes > 0 {
+        extra_meta.push((
+            format!("{name}_removed_bytes"),
+            stats.removed_bytes.to_string(),
+        ));
+    }
+    if stats.collapsed_lines > 0 {
+        extra_meta.push((
+            format!("{name}_collapsed_lines"),
+            stats.collapsed_lines.to_string(),
+        ));
     }
-    drop(lock_file);
+    out
+}
 
-    rommy_note_cyan(colors, &format!("Wrote {}", out_path.display()));
-    Ok(())
+/// Returns (bytes for the STDOUT/STDERR block, optional verbatim bytes for the *_ANSI block).
+fn apply_ansi_mode(mode: AnsiMode, raw: Vec<u8>) -> (Vec<u8>, Option<Vec<u8>>) {
+    match mode {
+        AnsiMode::Keep => (raw, None),
+        AnsiMode::Strip => (ansi::strip(&raw), None),
+        AnsiMode::Both => {
+            let stripped = ansi::strip(&raw);
+            // Only keep a second copy if there was actually something to strip
+            if stripped == raw {
+                (stripped, None)
+            } else {
+                (stripped, Some(raw))
+            }
+        }
+    }
 }
 
 fn collect_rommy_files(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
@@ -521,12 +1439,7 @@ fn collect_rommy_files(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
             let m = entry.metadata()?;
             if m.is_dir() {
                 collect_rommy_files(&p, out)?;
-            } else if p
-                .extension()
-                .and_then(|s| s.to_str())
-                .map(|ext| ext.eq_ignore_ascii_case("rommy"))
-                .unwrap_or(false)
-            {
+            } else if compression::is_rommy_path(&p) {
                 out.push(p);
             }
         }
@@ -540,6 +1453,53 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
         path: String,
         records: Option<usize>,
         error: Option<String>,
+        error_kind: Option<&'static str>,
+        position: Option<Position>,
+        /// Findings of `--strict`
+        issues: Vec<strict::Issue>,
+    }
+
+    impl ValidationEntry {
+        fn is_valid(&self) -> bool {
+            self.error.is_none() && self.issues.iter().all(|i| i.level != strict::Level::Error)
+        }
+
+        fn to_json(&self) -> serde_json::Value {
+            json!({
+                "path": self.path,
+                "valid": self.is_valid(),
+                "records": self.records,
+                "error": self.error,
+                "error_kind": self.error_kind,
+                "line": self.position.map(|at| at.line),
+                "record": self.position.map(|at| at.record),
+                "offset": self.position.map(|at| at.offset),
+                "issues": self.issues.iter().map(strict::Issue::to_json).collect::<Vec<_>>(),
+            })
+        }
+
+        /// `file:line` where the problem is, or just the file
+        fn location(&self) -> String {
+            match self.position {
+                Some(at) => format!("{}:{}", self.path, at.line),
+                None => self.path.clone(),
+            }
+        }
+    }
+
+    /// Read all records of `file`, feeding them to `checker` in strict mode.
+    fn check_file(file: &Path, checker: Option<&mut strict::Checker>) -> Result<usize> {
+        let reader = RecordReader::open(file)?.strict_meta(checker.is_some());
+        let mut checker = checker;
+        let mut count = 0;
+        for record in reader {
+            let record = record?;
+            count += 1;
+            if let Some(checker) = checker.as_mut() {
+                checker.check(count, &record);
+            }
+        }
+        Ok(count)
     }
 
     let mut files = Vec::new();
@@ -557,23 +1517,42 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
     let mut entries = Vec::with_capacity(files.len());
 
     for file in &files {
-        match rommy::parser::parse_file(file) {
-            Ok(records) => {
-                ok_count += 1;
-                entries.push(ValidationEntry {
-                    path: file.display().to_string(),
-                    records: Some(records.len()),
-                    error: None,
-                });
-            }
+        // Records are checked one at a time, so large append logs need little memory
+        let mut checker = strict::Checker::default();
+        let counted = check_file(file, cfg.strict.then_some(&mut checker));
+        let entry = match counted {
+            Ok(records) => ValidationEntry {
+                path: file.display().to_string(),
+                records: Some(records),
+                error: None,
+                error_kind: None,
+                position: None,
+                issues: checker.issues,
+            },
             Err(err) => {
-                err_count += 1;
-                entries.push(ValidationEntry {
+                let parse_error = err.downcast_ref::<ParseError>();
+                ValidationEntry {
                     path: file.display().to_string(),
                     records: None,
-                    error: Some(err.to_string()),
-                });
+                    error: Some(match parse_error {
+                        Some(parse_error) => parse_error.message(),
+                        None => format!("{err:#}"),
+                    }),
+                    error_kind: Some(parse_error.map_or("io", ParseError::kind)),
+                    position: parse_error.and_then(ParseError::position),
+                    issues: checker.issues,
+                }
             }
+        };
+        if entry.is_valid() {
+            ok_count += 1;
+        } else {
+            err_count += 1;
+        }
+        if matches!(cfg.format, ValidateFormat::Ndjson) {
+            println!("{}", entry.to_json());
+        } else {
+            entries.push(entry);
         }
     }
 
@@ -581,8 +1560,19 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
         ValidateFormat::Text => {
             for entry in &entries {
                 if let Some(err) = &entry.error {
-                    eprintln!("ERR {}: {}", entry.path, err);
-                } else if !cfg.quiet {
+                    eprintln!("ERR {}: {}", entry.location(), err);
+                }
+                for issue in &entry.issues {
+                    let tag = match issue.level {
+                        strict::Level::Error => "ERR",
+                        strict::Level::Warning => "WARN",
+                    };
+                    eprintln!(
+                        "{} {}#{}: {} [{}]",
+                        tag, entry.path, issue.record, issue.message, issue.code
+                    );
+                }
+                if entry.is_valid() && !cfg.quiet {
                     let records = entry.records.unwrap_or(0);
                     println!("OK {} ({} record(s))", entry.path, records);
                 }
@@ -597,17 +1587,7 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
             }
         }
         ValidateFormat::Json => {
-            let file_values: Vec<_> = entries
-                .into_iter()
-                .map(|entry| {
-                    json!({
-                        "path": entry.path,
-                        "valid": entry.error.is_none(),
-                        "records": entry.records,
-                        "error": entry.error,
-                    })
-                })
-                .collect();
+            let file_values: Vec<_> = entries.iter().map(ValidationEntry::to_json).collect();
             println!(
                 "{}",
                 serde_json::to_string_pretty(&json!({
@@ -618,6 +1598,8 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
                 }))?
             );
         }
+        // Already written while checking
+        ValidateFormat::Ndjson => {}
     }
 
     if err_count > 0 {
@@ -630,15 +1612,38 @@ fn validate(cfg: ValidateConfig) -> Result<()> {
     Ok(())
 }
 
-fn print_record_text(record_index: usize, record: &rommy::parser::RommyRecord) {
+fn record_json(record_index: usize, record: &rommy::parser::RommyRecord) -> serde_json::Value {
+    json!({
+        "record": record_index,
+        "meta": record.meta.to_json(),
+        "meta_entries": record.meta.to_json_entries(),
+        "command": record.command,
+        "stdout": record.stdout,
+        "stderr": record.stderr,
+        "stdout_ansi": record.stdout_ansi,
+        "stderr_ansi": record.stderr_ansi,
+        "timing": record.timing,
+    })
+}
+
+/// One `show --format ndjson` line.
+fn write_ndjson(
+    out: &mut impl Write,
+    record_index: usize,
+    record: &rommy::parser::RommyRecord,
+    path: &str,
+) -> Result<()> {
+    let mut item = record_json(record_index, record);
+    item["path"] = json!(path);
+    writeln!(out, "{item}")?;
+    Ok(())
+}
+
+fn print_record_text(record_index: usize, record: &rommy::parser::RommyRecord, ansi: ShowAnsi) {
     println!("=== Record {} ===", record_index);
     println!("<<<META>>>");
-    let mut keys: Vec<_> = record.meta.keys().collect();
-    keys.sort();
-    for key in keys {
-        if let Some(value) = record.meta.get(key) {
-            println!("{}: {}", key, value);
-        }
+    for (key, value) in record.meta.iter() {
+        println!("{}: {}", key, value);
     }
     println!("<<<END>>>");
 
@@ -649,67 +1654,109 @@ fn print_record_text(record_index: usize, record: &rommy::parser::RommyRecord) {
     println!("<<<END>>>");
 
     println!("<<<STDOUT>>>");
-    if !record.stdout.is_empty() {
-        println!("{}", record.stdout);
-    }
+    print_output_text(&record.stdout, record.stdout_ansi.as_deref(), ansi);
     println!("<<<END>>>");
 
     println!("<<<STDERR>>>");
-    if !record.stderr.is_empty() {
-        println!("{}", record.stderr);
-    }
+    print_output_text(&record.stderr, record.stderr_ansi.as_deref(), ansi);
     println!("<<<END>>>");
 }
 
+fn print_output_text(plain: &str, verbatim: Option<&str>, ansi: ShowAnsi) {
+    match ansi {
+        ShowAnsi::Render => {
+            let text = verbatim.unwrap_or(plain);
+            if text.is_empty() {
+                return;
+            }
+            if ansi::has_escapes(text) {
+                // Reset afterwards so stored colors cannot bleed into the END marker
+                println!("{text}{RESET}");
+            } else {
+                println!("{text}");
+            }
+        }
+        ShowAnsi::Strip => {
+            if !plain.is_empty() {
+                println!("{}", ansi::strip_str(plain));
+            }
+        }
+    }
+}
+
 fn show(cfg: ShowConfig) -> Result<()> {
-    let records = rommy::parser::parse_file(&cfg.path)
-        .with_context(|| format!("failed to parse {}", cfg.path.display()))?;
+    anyhow::ensure!(
+        cfg.budget.is_none() || matches!(cfg.format, ShowFormat::Markdown),
+        "--budget requires --format markdown"
+    );
+    let parse_context = || format!("failed to parse {}", cfg.path.display());
+
+    // NDJSON of a whole file: each record is printed as soon as it is read
+    if cfg.record.is_none() && matches!(cfg.format, ShowFormat::Ndjson) {
+        let path = cfg.path.display().to_string();
+        let mut out = io::stdout().lock();
+        let mut count = 0;
+        for record in RecordReader::open(&cfg.path).with_context(parse_context)? {
+            let record = record.with_context(parse_context)?;
+            count += 1;
+            write_ndjson(&mut out, count, &record, &path)?;
+        }
+        anyhow::ensure!(count > 0, "No records found in {}", cfg.path.display());
+        return Ok(());
+    }
+
+    let records: Vec<(usize, rommy::parser::RommyRecord)> = if let Some(record_number) = cfg.record
+    {
+        anyhow::ensure!(record_number > 0, "--record must be >= 1");
+        // Stop reading as soon as the record is found
+        let mut count = 0;
+        let mut found = None;
+        for record in RecordReader::open(&cfg.path).with_context(parse_context)? {
+            let record = record.with_context(parse_context)?;
+            count += 1;
+            if count == record_number {
+                found = Some(record);
+                break;
+            }
+        }
+        anyhow::ensure!(count > 0, "No records found in {}", cfg.path.display());
+        let record = found
+            .with_context(|| format!("--record {record_number} is out of range (1..={count})"))?;
+        vec![(record_number, record)]
+    } else {
+        rommy::parser::parse_file(&cfg.path)
+            .with_context(parse_context)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| (i + 1, r))
+            .collect()
+    };
     anyhow::ensure!(
         !records.is_empty(),
         "No records found in {}",
         cfg.path.display()
     );
-
     let selected: Vec<(usize, &rommy::parser::RommyRecord)> =
-        if let Some(record_number) = cfg.record {
-            anyhow::ensure!(record_number > 0, "--record must be >= 1");
-            let idx = record_number - 1;
-            anyhow::ensure!(
-                idx < records.len(),
-                "--record {} is out of range (1..={})",
-                record_number,
-                records.len()
-            );
-            vec![(record_number, &records[idx])]
-        } else {
-            records
-                .iter()
-                .enumerate()
-                .map(|(i, r)| (i + 1, r))
-                .collect()
-        };
+        records.iter().map(|(i, r)| (*i, r)).collect();
 
     match cfg.format {
         ShowFormat::Text => {
+            let ansi = cfg.ansi.unwrap_or(if color_is_enabled(cfg.color) {
+                ShowAnsi::Render
+            } else {
+                ShowAnsi::Strip
+            });
             for (i, (record_index, record)) in selected.iter().enumerate() {
                 if i > 0 {
                     println!();
                 }
-                print_record_text(*record_index, record);
+                print_record_text(*record_index, record, ansi);
             }
         }
         ShowFormat::Json => {
             let items: Vec<_> = selected
                 .into_iter()
-                .map(|(record_index, record)| {
-                    json!({
-                        "record": record_index,
-                        "meta": record.meta,
-                        "command": record.command,
-                        "stdout": record.stdout,
-                        "stderr": record.stderr,
-                    })
-                })
+                .map(|(record_index, record)| record_json(record_index, record))
                 .collect();
             println!(
                 "{}",
@@ -719,16 +1766,24 @@ fn show(cfg: ShowConfig) -> Result<()> {
                 }))?
             );
         }
+        ShowFormat::Ndjson => {
+            let path = cfg.path.display().to_string();
+            let mut out = io::stdout().lock();
+            for (record_index, record) in selected {
+                write_ndjson(&mut out, record_index, record, &path)?;
+            }
+        }
+        ShowFormat::Markdown => {
+            print!(
+                "{}",
+                markdown::render(&cfg.path.display().to_string(), &selected, cfg.budget)
+            );
+        }
     }
 
     Ok(())
 }
 
-enum RommyCommand {
-    Line(String),
-    Script { path: PathBuf, content: String },
-}
-
 /// Join argv-like pieces into a bash-safe single line for display/execution with bash -lc.
 /// Minimal approach: quote each arg safely.
 fn shell_join<S: AsRef<OsStr>>(parts: &[S]) -> Result<String> {
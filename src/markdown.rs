@@ -0,0 +1,177 @@
+use rommy::parser::RommyRecord;
+use std::fmt::Write as _;
+
+use crate::ansi;
+use crate::progress::format_bytes;
+
+/// Rough size of a token, used to turn `--budget` into characters.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Room left for the "[… N line(s), X omitted …]" marker when cutting.
+const MARKER_CHARS: usize = 40;
+
+/// META keys worth showing, with their labels, in this order.
+const KEY_META: [(&str, &str); 7] = [
+    ("exit_code", "exit code"),
+    ("status", "status"),
+    ("duration_ms", "duration (ms)"),
+    ("cwd", "cwd"),
+    ("start_ts", "started"),
+    ("label", "label"),
+    ("git_commit", "git commit"),
+];
+
+/// A backtick fence longer than any backtick run in `content`.
+fn fence_for(content: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in content.chars() {
+        if c == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+fn push_fenced(out: &mut String, info: &str, content: &str) {
+    let fence = fence_for(content);
+    let _ = writeln!(out, "{fence}{info}");
+    if !content.is_empty() {
+        out.push_str(content);
+        if !content.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    let _ = writeln!(out, "{fence}");
+}
+
+/// Keep the first and last lines of `text` within about `max_chars`,
+/// replacing the middle with a marker line.
+fn head_tail(text: &str, max_chars: usize) -> String {
+    if text.len() <= max_chars {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let half = max_chars.saturating_sub(MARKER_CHARS) / 2;
+    let mut head = 0;
+    let mut used = 0;
+    while head < lines.len() && used + lines[head].len() < half {
+        used += lines[head].len() + 1;
+        head += 1;
+    }
+    let mut tail = 0;
+    used = 0;
+    while tail < lines.len() - head && used + lines[lines.len() - 1 - tail].len() < half {
+        used += lines[lines.len() - 1 - tail].len() + 1;
+        tail += 1;
+    }
+    let omitted = &lines[head..lines.len() - tail];
+    let omitted_bytes: usize = omitted.iter().map(|l| l.len() + 1).sum();
+    let mut out = String::new();
+    for line in &lines[..head] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    let _ = writeln!(
+        out,
+        "[… {} line(s), {} omitted …]",
+        omitted.len(),
+        format_bytes(omitted_bytes)
+    );
+    for line in &lines[lines.len() - tail..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Share `available` characters between blocks of the given sizes: small blocks
+/// keep everything, the rest split what is left evenly.
+fn allot(sizes: &[usize], available: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| sizes[i]);
+    let mut shares = vec![0; sizes.len()];
+    let mut left = available;
+    for (n, &i) in order.iter().enumerate() {
+        let fair = left / (sizes.len() - n);
+        shares[i] = sizes[i].min(fair);
+        left -= shares[i];
+    }
+    shares
+}
+
+fn render_record(out: &mut String, record_index: usize, record: &RommyRecord, outputs: [&str; 2]) {
+    match record.meta.get("label") {
+        Some(label) => {
+            let _ = writeln!(out, "## Record {record_index}: {label}");
+        }
+        None => {
+            let _ = writeln!(out, "## Record {record_index}");
+        }
+    }
+    out.push('\n');
+    for (key, title) in KEY_META {
+        if let Some(value) = record.meta.get(key) {
+            let _ = writeln!(out, "- **{title}:** {value}");
+        }
+    }
+    out.push('\n');
+    out.push_str("### Command\n\n");
+    push_fenced(out, "bash", &record.command);
+    for (name, text) in ["stdout", "stderr"].into_iter().zip(outputs) {
+        // An empty stderr is the common case and not worth a heading
+        if name == "stderr" && text.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "\n### {name}\n");
+        push_fenced(out, "text", text);
+    }
+}
+
+/// Render records as Markdown for pasting into a chat. With `budget` (in
+/// approximate tokens), long outputs are cut down to their head and tail.
+/// Escape sequences kept by `--ansi keep` are removed; a chat shows them as noise.
+pub fn render(path: &str, records: &[(usize, &RommyRecord)], budget: Option<usize>) -> String {
+    let outputs: Vec<[String; 2]> = records
+        .iter()
+        .map(|(_, r)| [ansi::strip_str(&r.stdout), ansi::strip_str(&r.stderr)])
+        .collect();
+
+    let render_all = |outputs: &[[String; 2]]| {
+        let mut out = format!("# rommy: `{path}`\n");
+        for ((record_index, record), [stdout, stderr]) in records.iter().zip(outputs) {
+            out.push('\n');
+            render_record(&mut out, *record_index, record, [stdout, stderr]);
+        }
+        out
+    };
+
+    let Some(budget) = budget else {
+        return render_all(&outputs);
+    };
+
+    // Everything except the outputs is kept; the outputs share what remains.
+    // Non-empty outputs are stood in for by a newline so their headings and
+    // fences are counted too.
+    let placeholders: Vec<[String; 2]> = outputs
+        .iter()
+        .map(|block| {
+            block
+                .each_ref()
+                .map(|s| if s.is_empty() { "" } else { "\n" }.to_string())
+        })
+        .collect();
+    let overhead = render_all(&placeholders).len();
+    let available = (budget * CHARS_PER_TOKEN).saturating_sub(overhead);
+    let sizes: Vec<usize> = outputs.iter().flatten().map(|s| s.len()).collect();
+    let shares = allot(&sizes, available);
+    let cut: Vec<[String; 2]> = outputs
+        .iter()
+        .zip(shares.chunks(2))
+        .map(|([o, e], share)| [head_tail(o, share[0]), head_tail(e, share[1])])
+        .collect();
+    render_all(&cut)
+}
@@ -0,0 +1,295 @@
+//! The META block of a record.
+//!
+//! [`Meta`] keeps the `key: value` lines in file order, duplicates included,
+//! and offers typed accessors for the keys `rommy run` writes. A malformed
+//! value is reported as a [`MetaError`] rather than silently skipped.
+
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::ops::Index;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// META entries in the order they appear in the file.
+///
+/// Lookups return the last value of a key, as a reader of the file would
+/// expect; [`Meta::iter`] yields every entry, including repeated keys.
+///
+/// ```
+/// use rommy::meta::Meta;
+///
+/// let meta: Meta = [("status", "ok"), ("exit_code", "0"), ("status", "error")]
+///     .into_iter()
+///     .collect();
+/// assert_eq!(meta["status"], "error");
+/// assert_eq!(meta.keys().collect::<Vec<_>>(), ["status", "exit_code"]);
+/// assert_eq!(meta.duplicates(), ["status"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Meta {
+    entries: Vec<(String, String)>,
+}
+
+impl Meta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Last value of `key`.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Every value of `key`, in file order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// Set `key`: replaces the value [`Meta::get`] returns, or appends a new entry.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        match self.entries.iter_mut().rev().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value.into(),
+            None => self.entries.push((key, value.into())),
+        }
+    }
+
+    /// Append an entry, keeping earlier values of the same key.
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((key.into(), value.into()));
+    }
+
+    /// Remove every entry of `key`; returns the last value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let mut last = None;
+        self.entries.retain_mut(|(k, v)| {
+            if k == key {
+                last = Some(std::mem::take(v));
+                false
+            } else {
+                true
+            }
+        });
+        last
+    }
+
+    /// All entries in file order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Distinct keys, in the order of their first appearance.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(i, (k, _))| !self.entries[..*i].iter().any(|(p, _)| p == k))
+            .map(|(_, (k, _))| k.as_str())
+    }
+
+    /// Keys that appear more than once, in the order of their first appearance.
+    pub fn duplicates(&self) -> Vec<&str> {
+        self.keys()
+            .filter(|key| self.get_all(key).nth(1).is_some())
+            .collect()
+    }
+
+    /// Number of entries, duplicates included.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// JSON array of `[key, value]` pairs: every entry, in file order.
+    pub fn to_json_entries(&self) -> serde_json::Value {
+        self.iter()
+            .map(|(k, v)| serde_json::json!([k, v]))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// JSON object of the last value of each key. JSON objects have no order;
+    /// [`Meta::to_json_entries`] keeps it.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.keys()
+            .map(|key| (key.to_string(), self[key].clone().into()))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    fn parsed<T: FromStr>(
+        &self,
+        key: &str,
+        expected: &'static str,
+    ) -> Result<Option<T>, MetaError> {
+        self.get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| MetaError::new(key, value, expected))
+            })
+            .transpose()
+    }
+
+    /// `exit_code` of the command.
+    pub fn exit_code(&self) -> Result<Option<i32>, MetaError> {
+        self.parsed("exit_code", "an integer")
+    }
+
+    fn timestamp(&self, key: &str) -> Result<Option<DateTime<Utc>>, MetaError> {
+        self.get(key)
+            .map(|value| {
+                DateTime::parse_from_rfc3339(value)
+                    .map(|ts| ts.with_timezone(&Utc))
+                    .map_err(|_| MetaError::new(key, value, "an RFC 3339 timestamp"))
+            })
+            .transpose()
+    }
+
+    /// `start_ts`: when the command started.
+    pub fn start(&self) -> Result<Option<DateTime<Utc>>, MetaError> {
+        self.timestamp("start_ts")
+    }
+
+    /// `end_ts`: when the command finished.
+    pub fn end(&self) -> Result<Option<DateTime<Utc>>, MetaError> {
+        self.timestamp("end_ts")
+    }
+
+    /// `duration_ms` as a [`Duration`].
+    pub fn duration(&self) -> Result<Option<Duration>, MetaError> {
+        Ok(self
+            .parsed::<u64>("duration_ms", "a non-negative integer")?
+            .map(Duration::from_millis))
+    }
+
+    /// `status` of the run.
+    pub fn status(&self) -> Result<Option<Status>, MetaError> {
+        self.parsed("status", "a known status")
+    }
+
+    /// `label` given with `rommy run --label`.
+    pub fn label(&self) -> Option<&str> {
+        self.get("label").map(String::as_str)
+    }
+}
+
+impl Index<&str> for Meta {
+    type Output = String;
+
+    /// Last value of `key`; panics if the key is missing.
+    fn index(&self, key: &str) -> &String {
+        self.get(key)
+            .unwrap_or_else(|| panic!("META key `{key}` is missing"))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Meta {
+    /// Entries in iteration order; repeated keys are kept.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Meta {
+            entries: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+/// A META value that does not have the expected form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaError {
+    pub key: String,
+    pub value: String,
+    /// What the value should have been, e.g. `an integer`
+    pub expected: &'static str,
+}
+
+impl MetaError {
+    fn new(key: &str, value: &str, expected: &'static str) -> Self {
+        MetaError {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected,
+        }
+    }
+}
+
+impl fmt::Display for MetaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is not {} ({:?})",
+            self.key, self.expected, self.value
+        )
+    }
+}
+
+impl std::error::Error for MetaError {}
+
+/// `status` values written by `rommy run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Error,
+    Killed,
+    LimitMem,
+    LimitCpu,
+    LimitFsize,
+    LimitNproc,
+}
+
+impl Status {
+    pub const ALL: [Status; 7] = [
+        Status::Ok,
+        Status::Error,
+        Status::Killed,
+        Status::LimitMem,
+        Status::LimitCpu,
+        Status::LimitFsize,
+        Status::LimitNproc,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Error => "error",
+            Status::Killed => "killed",
+            Status::LimitMem => "limit_mem",
+            Status::LimitCpu => "limit_cpu",
+            Status::LimitFsize => "limit_fsize",
+            Status::LimitNproc => "limit_nproc",
+        }
+    }
+}
+
+impl FromStr for Status {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Status::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{Context, Result};
+use rommy::compression::{self, Compression};
+use rommy::meta::Meta;
+use rommy::parser::FORMAT_VERSION;
+use rommy::writer::{self, RecordLock};
+use std::path::Path;
+
+use crate::{MigrateConfig, collect_rommy_files, color_is_enabled, diff, index, rommy_note_cyan};
+
+/// Line content without its line break.
+fn content(line: &str) -> &str {
+    line.trim_end_matches('\n').trim_end_matches('\r')
+}
+
+/// The key of a `key: value` META line.
+fn meta_key(line: &str) -> Option<&str> {
+    content(line).split_once(':').map(|(k, _)| k.trim())
+}
+
+/// New lines of one META block: lines whose value changed are rewritten in
+/// place, keys the upgrade dropped are removed, new keys follow at the end.
+/// Blank lines, lines without a colon and repeated keys stay as they were.
+fn rewrite_meta(lines: &[&str], meta: &Meta, eol: &str) -> Vec<String> {
+    let had_version = lines.iter().any(|l| meta_key(l) == Some("format_version"));
+    let version_line = format!("format_version: {FORMAT_VERSION}{eol}");
+    let mut out = Vec::new();
+    if !had_version && !lines.iter().any(|l| meta_key(l) == Some("rommy_version")) {
+        out.push(version_line.clone());
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let Some(key) = meta_key(line) else {
+            out.push(line.to_string());
+            continue;
+        };
+        let Some(value) = meta.get(key) else {
+            continue;
+        };
+        let last = !lines[i + 1..].iter().any(|l| meta_key(l) == Some(key));
+        let old = content(line).split_once(':').map_or("", |(_, v)| v.trim());
+        if last && old != value {
+            out.push(format!("{key}: {value}{eol}"));
+        } else {
+            out.push(line.to_string());
+        }
+        if key == "rommy_version" && !had_version {
+            out.push(version_line.clone());
+        }
+    }
+    for key in meta.keys() {
+        if key != "format_version" && !lines.iter().any(|l| meta_key(l) == Some(key)) {
+            out.push(format!("{key}: {}{eol}", meta[key]));
+        }
+    }
+    out
+}
+
+/// `text` with every META block in the current layout. Everything outside
+/// META (block contents, lines between blocks) is copied byte for byte.
+fn migrate_text(text: &str) -> Result<String> {
+    // The parser lifts older layouts to the current one and rejects broken files
+    let records = rommy::parser::parse_str(text)?;
+    let mut records = records.iter();
+    let mut out = String::with_capacity(text.len());
+    let mut meta_lines: Option<(Vec<&str>, &str)> = None;
+    let (mut in_block, mut seen_meta) = (false, false);
+    for line in text.split_inclusive('\n') {
+        let is_end = content(line).trim() == "<<<END>>>";
+        if let Some((lines, eol)) = meta_lines.as_mut() {
+            if is_end {
+                let record = records.next().context("META block without a record")?;
+                let mut meta = record.meta.clone();
+                meta.insert("format_version", FORMAT_VERSION.to_string());
+                for new_line in rewrite_meta(lines, &meta, eol) {
+                    out.push_str(&new_line);
+                }
+                out.push_str(line);
+                meta_lines = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        out.push_str(line);
+        if in_block {
+            in_block = !is_end;
+        } else if let Some(block) = rommy::parser::block_marker(content(line)) {
+            if block == "META" {
+                let eol = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+                meta_lines = Some((Vec::new(), eol));
+                seen_meta = true;
+            } else {
+                // Markers before the first META are noise to the parser as well
+                in_block = seen_meta;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Upgrade one file; returns the diff from old to new content, or `None` if it is current.
+fn migrate_file(path: &Path, dry_run: bool) -> Result<Option<String>> {
+    // Same lock as `run`, so no record is appended while the file is rewritten;
+    // a dry run only reads and leaves no lock file behind
+    let lock = if dry_run {
+        None
+    } else {
+        Some(RecordLock::acquire(path)?)
+    };
+    let old = compression::read_to_string(path)?;
+    let new = migrate_text(&old)?;
+
+    let name = path.display().to_string();
+    let Some(changes) = diff::unified(&old, &new, &name, &format!("{name} (migrated)")) else {
+        return Ok(None);
+    };
+    if let Some(lock) = &lock {
+        let encoded = Compression::from_path(path).encode(new.as_bytes())?;
+        writer::replace_file(path, &encoded, false, lock)?;
+        if let Err(err) = index::update_after_write(path) {
+            eprintln!("WARN {}: index not updated: {err:#}", path.display());
+        }
+    }
+    Ok(Some(changes))
+}
+
+/// `rommy migrate`: rewrite files in the current format version.
+pub fn migrate(cfg: MigrateConfig) -> Result<()> {
+    let colors = color_is_enabled(cfg.color);
+    let mut files = Vec::new();
+    for path in &cfg.paths {
+        collect_rommy_files(path, &mut files)?;
+    }
+    files.sort();
+    files.dedup();
+    anyhow::ensure!(!files.is_empty(), "No files found to migrate");
+
+    let (mut migrated, mut failed) = (0usize, 0usize);
+    for file in &files {
+        match migrate_file(file, cfg.dry_run) {
+            Ok(Some(changes)) => {
+                migrated += 1;
+                if cfg.dry_run {
+                    print!("{changes}");
+                } else {
+                    println!("Migrated {}", file.display());
+                }
+            }
+            Ok(None) => {}
+            Err(err) => {
+                failed += 1;
+                eprintln!("ERR {}: {:#}", file.display(), err);
+            }
+        }
+    }
+    let verb = if cfg.dry_run {
+        "Would migrate"
+    } else {
+        "Migrated"
+    };
+    rommy_note_cyan(
+        colors,
+        &format!(
+            "{} {} of {} file(s) to format_version {}",
+            verb,
+            migrated,
+            files.len(),
+            FORMAT_VERSION
+        ),
+    );
+    if failed > 0 {
+        anyhow::bail!("{failed} file(s) could not be migrated");
+    }
+    Ok(())
+}
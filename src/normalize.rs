@@ -0,0 +1,160 @@
+/// What the normaliser removed from one stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeStats {
+    /// Bytes saved compared to the raw capture
+    pub removed_bytes: usize,
+    /// Lines folded away into "(repeated N×)" markers
+    pub collapsed_lines: usize,
+}
+
+/// Apply carriage returns like a terminal would and collapse runs of identical lines.
+/// Progress bars (cargo, pip, curl) redraw a line with `\r`; only its final state is kept.
+pub fn normalize(input: &[u8]) -> (Vec<u8>, NormalizeStats) {
+    if input.is_empty() {
+        return (Vec::new(), NormalizeStats::default());
+    }
+    let ends_with_newline = input.ends_with(b"\n");
+    let body = if ends_with_newline {
+        &input[..input.len() - 1]
+    } else {
+        input
+    };
+
+    let lines: Vec<Vec<u8>> = body
+        .split(|&b| b == b'\n')
+        .map(|line| {
+            if line.contains(&b'\r') {
+                apply_carriage_returns(line)
+            } else {
+                line.to_vec()
+            }
+        })
+        .collect();
+
+    let mut stats = NormalizeStats::default();
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        let mut run = 1;
+        while i + run < lines.len() && lines[i + run] == *line {
+            run += 1;
+        }
+
+        let marker = format!("(repeated {run}×)");
+        // Only fold when it actually saves space (and never for blank lines).
+        let saved = (run - 1) * (line.len() + 1);
+        if run > 2 && !line.iter().all(u8::is_ascii_whitespace) && saved > marker.len() + 1 {
+            out.extend_from_slice(line);
+            out.push(b'\n');
+            out.extend_from_slice(marker.as_bytes());
+            stats.collapsed_lines += run - 1;
+        } else {
+            for (k, l) in lines[i..i + run].iter().enumerate() {
+                if k > 0 {
+                    out.push(b'\n');
+                }
+                out.extend_from_slice(l);
+            }
+        }
+        i += run;
+        if i < lines.len() {
+            out.push(b'\n');
+        }
+    }
+    if ends_with_newline {
+        out.push(b'\n');
+    }
+
+    stats.removed_bytes = input.len().saturating_sub(out.len());
+    (out, stats)
+}
+
+/// Emulate a single terminal line: `\r` moves the cursor to column 0, later text overwrites
+/// earlier text, and `ESC[K` erases. Other escape sequences are zero-width and stay attached
+/// to the character that follows them, so colors survive. Works on bytes: a byte that is not
+/// valid UTF-8 takes one column and is kept as it is.
+fn apply_carriage_returns(line: &[u8]) -> Vec<u8> {
+    struct Cell<'a> {
+        prefix: Vec<u8>,
+        glyph: &'a [u8],
+    }
+
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut cursor = 0usize;
+    let mut pending = Vec::new();
+
+    let mut i = 0;
+    while i < line.len() {
+        match line[i] {
+            b'\r' => {
+                cursor = 0;
+                i += 1;
+            }
+            0x1b if line.get(i + 1) == Some(&b'[') => {
+                let params_start = i + 2;
+                let fin = line[params_start..]
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                    .map(|p| params_start + p);
+                let Some(fin) = fin else {
+                    // Unterminated sequence: keep it as it is
+                    pending.extend_from_slice(&line[i..]);
+                    break;
+                };
+                match line[fin] {
+                    b'K' => match &line[params_start..fin] {
+                        b"" | b"0" => cells.truncate(cursor),
+                        b"1" => {
+                            for cell in cells.iter_mut().take(cursor + 1) {
+                                cell.glyph = b" ";
+                            }
+                        }
+                        _ => cells.clear(),
+                    },
+                    _ => pending.extend_from_slice(&line[i..=fin]),
+                }
+                i = fin + 1;
+            }
+            _ => {
+                let len = glyph_len(&line[i..]);
+                while cells.len() < cursor {
+                    cells.push(Cell {
+                        prefix: Vec::new(),
+                        glyph: b" ",
+                    });
+                }
+                let cell = Cell {
+                    prefix: std::mem::take(&mut pending),
+                    glyph: &line[i..i + len],
+                };
+                if cursor < cells.len() {
+                    cells[cursor] = cell;
+                } else {
+                    cells.push(cell);
+                }
+                cursor += 1;
+                i += len;
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(line.len());
+    for cell in &cells {
+        out.extend_from_slice(&cell.prefix);
+        out.extend_from_slice(cell.glyph);
+    }
+    out.extend_from_slice(&pending);
+    out
+}
+
+/// Length of the character at the start of `bytes`; an invalid sequence counts as one.
+fn glyph_len(bytes: &[u8]) -> usize {
+    match bytes.utf8_chunks().next() {
+        Some(chunk) => match chunk.valid().chars().next() {
+            Some(c) => c.len_utf8(),
+            None => chunk.invalid().len().max(1),
+        },
+        None => 1,
+    }
+}
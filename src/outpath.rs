@@ -1,4 +1,4 @@
-use chrono::Local;
+use chrono::{DateTime, Local};
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -46,10 +46,10 @@ pub fn default_root_dir() -> PathBuf {
 }
 
 /// Erzeuge den finalen Pfad (und legt benötigte Ordner an), wenn --out nicht gesetzt ist.
+/// `now` bestimmt Datumsordner und Uhrzeit (bei Importen die Zeit der Aufnahme).
 /// Beispiel: <root>/2025/10/20/173048.cargo_clippy.rommy
-pub fn resolve_auto_out_path(cmd_display: &str) -> std::io::Result<PathBuf> {
+pub fn resolve_auto_out_path(cmd_display: &str, now: DateTime<Local>) -> std::io::Result<PathBuf> {
     let root = default_root_dir();
-    let now = Local::now();
     let yyyy = now.format("%Y").to_string();
     let mm = now.format("%m").to_string();
     let dd = now.format("%d").to_string();
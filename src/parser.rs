@@ -1,18 +1,79 @@
-use anyhow::{Context, Result, bail};
-use std::collections::HashMap;
-use std::fs;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::io::{self, BufRead};
 use std::path::Path;
+use std::time::Duration;
+
+use crate::meta::{Meta, MetaError, Status};
+
+/// Aktuelle Version des META-Layouts (`format_version`).
+///
+/// - 1: frühes Layout mit `timestamp`, `exit_code`, `duration_ms`
+/// - 2: `start_ts`, `end_ts`, `duration_ms`, `status`, `exit_code`, ...
+///
+/// Ältere Records werden beim Lesen auf das aktuelle Layout gehoben;
+/// `rommy migrate` schreibt sie so zurück.
+pub const FORMAT_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RommyRecord {
-    /// META als Key→Value (letzter Eintrag gewinnt bei Duplikaten)
-    pub meta: HashMap<String, String>,
+    /// META in Dateireihenfolge (Duplikate bleiben erhalten). Ein älteres
+    /// Layout ist hier schon auf [`FORMAT_VERSION`] gehoben; welches in der
+    /// Datei steht, sagt [`RommyRecord::format_version`].
+    pub meta: Meta,
     /// Der im COMMAND-Block angezeigte Inhalt (Bash-Zeile oder Script-Text)
     pub command: String,
     /// Rohes STDOUT
     pub stdout: String,
     /// Rohes STDERR
     pub stderr: String,
+    /// STDOUT mit ANSI-Escapes, falls mit `--ansi both` aufgezeichnet
+    pub stdout_ansi: Option<String>,
+    /// STDERR mit ANSI-Escapes, falls mit `--ansi both` aufgezeichnet
+    pub stderr_ansi: Option<String>,
+    /// Zeitpunkte der Ausgabe-Chunks, falls mit `--timing` aufgezeichnet
+    /// (eine Zeile `<ms> <o|e> <bytes>` pro Chunk)
+    pub timing: Option<String>,
+    /// Version des META-Layouts in der Datei, bevor `meta` beim Lesen auf
+    /// [`FORMAT_VERSION`] gehoben wurde
+    pub source_version: u32,
+}
+
+/// Typisierte Sicht auf die META-Schlüssel, die `rommy run` schreibt.
+/// Fehlt ein Schlüssel, ist das Ergebnis `Ok(None)`; ein unlesbarer Wert
+/// ergibt einen [`MetaError`].
+impl RommyRecord {
+    pub fn exit_code(&self) -> Result<Option<i32>, MetaError> {
+        self.meta.exit_code()
+    }
+
+    pub fn start(&self) -> Result<Option<DateTime<Utc>>, MetaError> {
+        self.meta.start()
+    }
+
+    pub fn end(&self) -> Result<Option<DateTime<Utc>>, MetaError> {
+        self.meta.end()
+    }
+
+    pub fn duration(&self) -> Result<Option<Duration>, MetaError> {
+        self.meta.duration()
+    }
+
+    pub fn status(&self) -> Result<Option<Status>, MetaError> {
+        self.meta.status()
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.meta.label()
+    }
+
+    /// Version des META-Layouts, in dem der Record gespeichert ist (siehe
+    /// [`fn@format_version`]). `meta` ist dagegen immer schon gehoben; 1 heißt,
+    /// dass `rommy migrate` die Datei noch umschreiben würde.
+    pub fn format_version(&self) -> u32 {
+        self.source_version
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,6 +82,9 @@ enum Block {
     Command,
     Stdout,
     Stderr,
+    StdoutAnsi,
+    StderrAnsi,
+    Timing,
 }
 
 impl Block {
@@ -30,249 +94,563 @@ impl Block {
             "<<<COMMAND>>>" => Some(Block::Command),
             "<<<STDOUT>>>" => Some(Block::Stdout),
             "<<<STDERR>>>" => Some(Block::Stderr),
+            "<<<STDOUT_ANSI>>>" => Some(Block::StdoutAnsi),
+            "<<<STDERR_ANSI>>>" => Some(Block::StderrAnsi),
+            "<<<TIMING>>>" => Some(Block::Timing),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Block::Meta => "META",
+            Block::Command => "COMMAND",
+            Block::Stdout => "STDOUT",
+            Block::Stderr => "STDERR",
+            Block::StdoutAnsi => "STDOUT_ANSI",
+            Block::StderrAnsi => "STDERR_ANSI",
+            Block::Timing => "TIMING",
+        }
+    }
+}
+
+/// Stelle in der Eingabe, an der ein Fehler gefunden wurde.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Zeilennummer (ab 1)
+    pub line: usize,
+    /// Nummer des betroffenen Records (ab 1; 0 vor dem ersten META)
+    pub record: usize,
+    /// Byte-Offset des Zeilenanfangs (in der entpackten Eingabe)
+    pub offset: u64,
+}
+
+/// Fehler beim Lesen einer .rommy-Datei.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Ein Block wurde bis zum Dateiende nicht mit <<<END>>> geschlossen
+    /// (Position: der öffnende Marker).
+    UnclosedBlock { block: &'static str, at: Position },
+    /// Einem Record fehlen Pflichtblöcke (Position: sein META-Marker).
+    MissingBlocks {
+        blocks: Vec<&'static str>,
+        at: Position,
+    },
+    /// Ein Marker innerhalb eines noch offenen Blocks.
+    UnexpectedMarker {
+        marker: &'static str,
+        open: &'static str,
+        at: Position,
+    },
+    /// Eine META-Zeile ohne `key: value` (nur mit [`RecordReader::strict_meta`]).
+    InvalidMetaLine { line: String, at: Position },
+    /// Eine Zeile ist kein gültiges UTF-8.
+    InvalidUtf8 { at: Position },
+    /// `format_version` ist unbekannt (neuer als diese Version von rommy)
+    /// oder keine Zahl (Position: der META-Marker).
+    UnsupportedVersion { version: String, at: Position },
+    /// Lesefehler (inklusive Entpacken).
+    Io(io::Error),
+}
+
+impl ParseError {
+    /// Fundstelle, sofern bekannt.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            ParseError::UnclosedBlock { at, .. }
+            | ParseError::MissingBlocks { at, .. }
+            | ParseError::UnexpectedMarker { at, .. }
+            | ParseError::InvalidMetaLine { at, .. }
+            | ParseError::InvalidUtf8 { at }
+            | ParseError::UnsupportedVersion { at, .. } => Some(*at),
+            ParseError::Io(_) => None,
+        }
+    }
+
+    /// Stabiler Bezeichner der Fehlerart, z.B. für JSON-Ausgaben.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseError::UnclosedBlock { .. } => "unclosed_block",
+            ParseError::MissingBlocks { .. } => "missing_blocks",
+            ParseError::UnexpectedMarker { .. } => "unexpected_marker",
+            ParseError::InvalidMetaLine { .. } => "invalid_meta_line",
+            ParseError::InvalidUtf8 { .. } => "invalid_utf8",
+            ParseError::UnsupportedVersion { .. } => "unsupported_version",
+            ParseError::Io(_) => "io",
+        }
+    }
+
+    /// Fehlermeldung ohne Position.
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnclosedBlock { block, .. } => {
+                format!("unexpected EOF: {block} block not closed with <<<END>>>")
+            }
+            ParseError::MissingBlocks { blocks, .. } => {
+                format!("incomplete record: missing block(s): {}", blocks.join(", "))
+            }
+            ParseError::UnexpectedMarker { marker, open, .. } => {
+                format!("unexpected start of block {marker} before closing {open} block")
+            }
+            ParseError::InvalidMetaLine { line, .. } => {
+                format!("invalid META line (expected `key: value`): {line}")
+            }
+            ParseError::InvalidUtf8 { .. } => "line is not valid UTF-8".to_string(),
+            ParseError::UnsupportedVersion { version, .. } => format!(
+                "unsupported format_version {version:?} (this rommy reads up to {FORMAT_VERSION})"
+            ),
+            ParseError::Io(err) => format!("failed to read input: {err}"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position() {
+            Some(at) => write!(f, "line {}: {}", at.line, self.message()),
+            None => f.write_str(&self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
             _ => None,
         }
     }
 }
 
 /// Parse eine .rommy-Datei in eine Liste von Records.
+/// Mit zstd oder gzip komprimierte Dateien werden transparent entpackt.
+/// Formatfehler lassen sich als [`ParseError`] per `downcast_ref` abfragen.
 pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<RommyRecord>> {
-    let text = fs::read_to_string(&path)
-        .with_context(|| format!("failed to read {}", path.as_ref().display()))?;
-    parse_str(&text)
+    Ok(RecordReader::open(path)?.collect::<Result<_, _>>()?)
 }
 
 /// Parse den Text-Inhalt (kann mehrere Records enthalten).
 pub fn parse_str(input: &str) -> Result<Vec<RommyRecord>> {
-    // Wir arbeiten zeilenbasiert, sind tolerant gegenüber CRLF und Leerzeilen
-    let input = input.replace("\r\n", "\n");
-    let lines = input.lines().peekable();
-
-    let mut out: Vec<RommyRecord> = Vec::new();
-
-    // Hilfsbuffer für gerade entstehenden Record
-    let mut cur_meta: Option<HashMap<String, String>> = None;
-    let mut cur_cmd = String::new();
-    let mut cur_stdout = String::new();
-    let mut cur_stderr = String::new();
-
-    // Zustandsmaschine innerhalb eines Records
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    enum State {
-        Idle,
-        InBlock(Block),
+    Ok(RecordReader::new(input.as_bytes()).collect::<Result<_, _>>()?)
+}
+
+// Zustandsmaschine innerhalb eines Records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    /// Offener Block und die Stelle seines Markers
+    InBlock(Block, Position),
+}
+
+/// Gerade entstehender Record
+#[derive(Debug)]
+struct Partial {
+    /// Stelle des META-Markers
+    start: Position,
+    meta: Meta,
+    cmd: String,
+    stdout: String,
+    stderr: String,
+    // Optionale Blöcke (nur bei `--ansi both` bzw. `--timing` vorhanden)
+    stdout_ansi: Option<String>,
+    stderr_ansi: Option<String>,
+    timing: Option<String>,
+    /// Hat der offene Block schon eine Zeile? (Eine leere erste Zeile zählt mit.)
+    has_line: bool,
+    // Welche Blöcke wurden korrekt mit <<<END>>> abgeschlossen?
+    saw_meta: bool,
+    saw_command: bool,
+    saw_stdout: bool,
+    saw_stderr: bool,
+}
+
+impl Partial {
+    fn new(start: Position) -> Self {
+        Partial {
+            start,
+            meta: Meta::new(),
+            cmd: String::new(),
+            stdout: String::new(),
+            stderr: String::new(),
+            stdout_ansi: None,
+            stderr_ansi: None,
+            timing: None,
+            has_line: false,
+            saw_meta: false,
+            saw_command: false,
+            saw_stdout: false,
+            saw_stderr: false,
+        }
     }
-    let mut state = State::Idle;
-
-    // Hilfsclosure: neuen leeren Record vorbereiten
-    let start_record = |cur_meta: &mut Option<HashMap<String, String>>,
-                        cur_cmd: &mut String,
-                        cur_stdout: &mut String,
-                        cur_stderr: &mut String| {
-        *cur_meta = Some(HashMap::new());
-        cur_cmd.clear();
-        cur_stdout.clear();
-        cur_stderr.clear();
-    };
-
-    // Welche Blöcke wurden im aktuellen Record korrekt mit <<<END>>> abgeschlossen?
-    let mut saw_meta = false;
-    let mut saw_command = false;
-    let mut saw_stdout = false;
-    let mut saw_stderr = false;
-
-    // Hilfsclosure: aktuellen Record beenden und pushen
-    let finish_record = |cur_meta: &mut Option<HashMap<String, String>>,
-                         cur_cmd: &mut String,
-                         cur_stdout: &mut String,
-                         cur_stderr: &mut String,
-                         saw_meta: bool,
-                         saw_command: bool,
-                         saw_stdout: bool,
-                         saw_stderr: bool,
-                         out: &mut Vec<RommyRecord>|
-     -> Result<()> {
-        let meta = cur_meta
-            .take()
-            .context("unexpected end of record: META missing")?;
+
+    /// Record abschließen; alle Pflichtblöcke müssen vorhanden sein.
+    fn finish(self) -> Result<RommyRecord, ParseError> {
         let mut missing = Vec::new();
-        if !saw_meta {
+        if !self.saw_meta {
             missing.push("META");
         }
-        if !saw_command {
+        if !self.saw_command {
             missing.push("COMMAND");
         }
-        if !saw_stdout {
+        if !self.saw_stdout {
             missing.push("STDOUT");
         }
-        if !saw_stderr {
+        if !self.saw_stderr {
             missing.push("STDERR");
         }
         if !missing.is_empty() {
-            bail!(
-                "incomplete record: missing block(s): {}",
-                missing.join(", ")
-            );
+            return Err(ParseError::MissingBlocks {
+                blocks: missing,
+                at: self.start,
+            });
         }
 
-        out.push(RommyRecord {
+        let mut meta = self.meta;
+        let source_version = upgrade_meta(&mut meta, &self.cmd).map_err(|version| {
+            ParseError::UnsupportedVersion {
+                version,
+                at: self.start,
+            }
+        })?;
+
+        Ok(RommyRecord {
             meta,
-            command: cur_cmd.clone(),
-            stdout: cur_stdout.clone(),
-            stderr: cur_stderr.clone(),
-        });
-        Ok(())
-    };
+            command: self.cmd,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            stdout_ansi: self.stdout_ansi,
+            stderr_ansi: self.stderr_ansi,
+            timing: self.timing,
+            source_version,
+        })
+    }
+}
+
+/// Version des META-Layouts; ohne `format_version` am Inhalt erkannt.
+/// Ein unlesbarer Wert wird als `Err` zurückgegeben.
+pub fn format_version(meta: &Meta) -> Result<u32, String> {
+    match meta.get("format_version") {
+        Some(v) => v.parse().map_err(|_| v.clone()),
+        None if meta.contains_key("timestamp") && !meta.contains_key("start_ts") => Ok(1),
+        None => Ok(FORMAT_VERSION),
+    }
+}
+
+/// META eines älteren Layouts auf [`FORMAT_VERSION`] heben; liefert die
+/// ursprüngliche Version.
+fn upgrade_meta(meta: &mut Meta, command: &str) -> Result<u32, String> {
+    let version = format_version(meta)?;
+    if version > FORMAT_VERSION {
+        return Err(version.to_string());
+    }
+    if version < 2 {
+        // 1 → 2: `timestamp` war der Start; Ende und Status werden abgeleitet
+        if let Some(start) = meta.remove("timestamp") {
+            let duration = meta
+                .get("duration_ms")
+                .and_then(|ms| ms.parse::<i64>().ok());
+            let end = DateTime::parse_from_rfc3339(&start)
+                .ok()
+                .zip(duration)
+                .map(|(ts, ms)| ts + chrono::Duration::milliseconds(ms));
+            if !meta.contains_key("start_ts") {
+                meta.insert("start_ts", start);
+            }
+            if let Some(end) = end
+                && !meta.contains_key("end_ts")
+            {
+                meta.insert("end_ts", end.to_rfc3339());
+            }
+        }
+        if !meta.contains_key("status")
+            && let Some(code) = meta.get("exit_code").and_then(|c| c.parse::<i32>().ok())
+        {
+            let status = if code == 0 { "ok" } else { "error" };
+            meta.insert("status", status);
+        }
+        if !meta.contains_key("command_line")
+            && !meta.contains_key("script_path")
+            && let Some(line) = command.strip_prefix("$ ")
+            && !line.contains('\n')
+        {
+            meta.insert("command_line", line);
+        }
+        // Was das alte Layout nicht kannte, wird wie bei Importen als unbekannt markiert
+        let mut unknown: Vec<String> = meta
+            .get("unknown_fields")
+            .map(|v| v.split(',').map(|k| k.trim().to_string()).collect())
+            .unwrap_or_default();
+        for key in ["rommy_version", "cwd", "user", "host"] {
+            if !meta.contains_key(key) && !unknown.iter().any(|k| k == key) {
+                unknown.push(key.to_string());
+            }
+        }
+        if !unknown.is_empty() {
+            meta.insert("unknown_fields", unknown.join(", "));
+        }
+        // Eine vorhandene Angabe beschreibt jetzt das gehobene Layout
+        if meta.contains_key("format_version") {
+            meta.insert("format_version", FORMAT_VERSION.to_string());
+        }
+    }
+    Ok(version)
+}
+
+/// Name des Blocks, den `line` öffnet (z.B. `"META"`), falls sie ein Marker ist.
+/// `<<<END>>>` ist kein öffnender Marker.
+pub fn block_marker(line: &str) -> Option<&'static str> {
+    Block::from_marker(line).map(Block::name)
+}
+
+/// Zeile an den Blockinhalt hängen; `has_line` unterscheidet eine leere erste
+/// Zeile von einem Block ohne Zeilen.
+fn push_line(buf: &mut String, has_line: &mut bool, line: &str) {
+    if *has_line {
+        buf.push('\n');
+    }
+    *has_line = true;
+    buf.push_str(line);
+}
+
+/// Liest Records einzeln aus einem `BufRead`, sodass auch sehr große
+/// (angehängte) Dateien mit konstantem Speicher verarbeitet werden.
+///
+/// ```
+/// use rommy::parser::RecordReader;
+///
+/// let text = "<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n$ true\n<<<END>>>\n\
+///             <<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+/// let mut reader = RecordReader::new(text.as_bytes());
+/// let record = reader.next().unwrap().unwrap();
+/// assert_eq!(record.meta["status"], "ok");
+/// assert!(reader.next().is_none());
+/// ```
+pub struct RecordReader<R> {
+    reader: R,
+    line: Vec<u8>,
+    /// Stelle der zuletzt gelesenen Zeile
+    at: Position,
+    /// Byte-Offset der nächsten Zeile
+    next_offset: u64,
+    state: State,
+    current: Option<Partial>,
+    /// Stelle des META-Markers des zuletzt gelieferten Records
+    last_start: Option<Position>,
+    strict_meta: bool,
+    done: bool,
+}
+
+impl RecordReader<Box<dyn BufRead>> {
+    /// Datei öffnen; zstd oder gzip wird transparent entpackt.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(RecordReader::new(crate::compression::open(path)?))
+    }
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub fn new(reader: R) -> Self {
+        RecordReader {
+            reader,
+            line: Vec::new(),
+            at: Position {
+                line: 0,
+                record: 0,
+                offset: 0,
+            },
+            next_offset: 0,
+            state: State::Idle,
+            current: None,
+            last_start: None,
+            strict_meta: false,
+            done: false,
+        }
+    }
+
+    /// META-Zeilen ohne Doppelpunkt als [`ParseError::InvalidMetaLine`] melden,
+    /// statt sie zu ignorieren.
+    pub fn strict_meta(mut self, strict: bool) -> Self {
+        self.strict_meta = strict;
+        self
+    }
+
+    /// Stelle des META-Markers des zuletzt gelieferten Records; `offset`
+    /// ist sein Byte-Offset in der (entpackten) Eingabe.
+    pub fn record_start(&self) -> Option<Position> {
+        self.last_start
+    }
+
+    /// Offenen Record abschließen, falls vorhanden.
+    fn complete(&mut self) -> Result<Option<RommyRecord>, ParseError> {
+        let Some(partial) = self.current.take() else {
+            return Ok(None);
+        };
+        self.last_start = Some(partial.start);
+        partial.finish().map(Some)
+    }
+
+    /// Zeilen lesen, bis ein Record vollständig ist (oder die Eingabe endet).
+    fn next_record(&mut self) -> Result<Option<RommyRecord>, ParseError> {
+        loop {
+            let mut line = std::mem::take(&mut self.line);
+            line.clear();
+            let read = self
+                .reader
+                .read_until(b'\n', &mut line)
+                .map_err(ParseError::Io)?;
+            if read == 0 {
+                return self.finish_input();
+            }
+            self.at.line += 1;
+            self.at.offset = self.next_offset;
+            self.next_offset += read as u64;
+            // Tolerant gegenüber CRLF
+            if line.ends_with(b"\n") {
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+            }
+            let result = match std::str::from_utf8(&line) {
+                Ok(text) => self.feed(text),
+                Err(_) => Err(ParseError::InvalidUtf8 { at: self.at }),
+            };
+            self.line = line;
+            if let Some(record) = result? {
+                return Ok(Some(record));
+            }
+        }
+    }
 
-    for raw_line in lines {
-        let line = raw_line;
+    /// Dateiende: Falls wir in einem offenen Block hängen, fehlt ein <<<END>>> Marker;
+    /// ein offener Record wird abgeschlossen.
+    fn finish_input(&mut self) -> Result<Option<RommyRecord>, ParseError> {
+        if let State::InBlock(block, at) = self.state {
+            return Err(ParseError::UnclosedBlock {
+                block: block.name(),
+                at,
+            });
+        }
+        self.complete()
+    }
 
+    /// Eine Zeile verarbeiten; liefert einen Record, wenn ein neuer META-Block
+    /// den vorherigen abschließt.
+    fn feed(&mut self, line: &str) -> Result<Option<RommyRecord>, ParseError> {
         // Marker?
         if let Some(block) = Block::from_marker(line) {
-            match state {
-                State::Idle => {
-                    match block {
-                        Block::Meta => {
-                            // Falls schon ein Record offen war, zuerst abschließen,
-                            // BEVOR wir einen neuen starten.
-                            if cur_meta.is_some() {
-                                finish_record(
-                                    &mut cur_meta,
-                                    &mut cur_cmd,
-                                    &mut cur_stdout,
-                                    &mut cur_stderr,
-                                    saw_meta,
-                                    saw_command,
-                                    saw_stdout,
-                                    saw_stderr,
-                                    &mut out,
-                                )?;
-                            }
-                            start_record(
-                                &mut cur_meta,
-                                &mut cur_cmd,
-                                &mut cur_stdout,
-                                &mut cur_stderr,
-                            );
-                            saw_meta = false;
-                            saw_command = false;
-                            saw_stdout = false;
-                            saw_stderr = false;
-                            state = State::InBlock(Block::Meta);
-                        }
-                        // Erlaube Folgeblöcke, wenn META bereits gesehen wurde
-                        Block::Command | Block::Stdout | Block::Stderr => {
-                            if cur_meta.is_some() {
-                                state = State::InBlock(block);
-                            } else {
-                                // Rauschen vor dem ersten META ignorieren
-                                continue;
-                            }
-                        }
+            if let State::InBlock(open, _) = self.state {
+                // Wir sind noch in einem Block und sehen sofort den nächsten Marker → Formatfehler
+                return Err(ParseError::UnexpectedMarker {
+                    marker: block.name(),
+                    open: open.name(),
+                    at: self.at,
+                });
+            }
+            if block == Block::Meta {
+                // Falls schon ein Record offen war, zuerst abschließen,
+                // BEVOR wir einen neuen starten.
+                let finished = self.complete()?;
+                self.at.record += 1;
+                self.current = Some(Partial::new(self.at));
+                self.state = State::InBlock(Block::Meta, self.at);
+                return Ok(finished);
+            }
+            // Erlaube Folgeblöcke, wenn META bereits gesehen wurde;
+            // Rauschen vor dem ersten META ignorieren
+            if let Some(cur) = self.current.as_mut() {
+                // Auch ein leerer optionaler Block ist vorhanden (Some(""))
+                match block {
+                    Block::StdoutAnsi => {
+                        cur.stdout_ansi.get_or_insert_with(String::new);
                     }
+                    Block::StderrAnsi => {
+                        cur.stderr_ansi.get_or_insert_with(String::new);
+                    }
+                    Block::Timing => {
+                        cur.timing.get_or_insert_with(String::new);
+                    }
+                    _ => {}
                 }
-                State::InBlock(_) => {
-                    // Wir sind noch in einem Block und sehen sofort den nächsten Marker → Formatfehler
-                    bail!(
-                        "unexpected start of block {:?} before closing previous block",
-                        block
-                    );
-                }
+                cur.has_line = false;
+                self.state = State::InBlock(block, self.at);
             }
-            continue;
+            return Ok(None);
         }
 
-        // Block-Ende?
+        // Block-Ende? (END ohne Block → ignoriere, Rauschen)
         if line.trim() == "<<<END>>>" {
-            match state {
-                State::Idle => {
-                    // END ohne Block → ignoriere (Rauschen)
-                    continue;
-                }
-                State::InBlock(_) => {
-                    match state {
-                        State::InBlock(Block::Meta) => saw_meta = true,
-                        State::InBlock(Block::Command) => saw_command = true,
-                        State::InBlock(Block::Stdout) => saw_stdout = true,
-                        State::InBlock(Block::Stderr) => saw_stderr = true,
-                        State::Idle => {}
-                    }
-                    // Ein Block endet; entweder geht's weiter mit nächstem Block,
-                    // oder ein neuer Record beginnt (wieder mit META), oder Datei endet.
-                    state = State::Idle;
+            if let (State::InBlock(block, _), Some(cur)) = (self.state, self.current.as_mut()) {
+                match block {
+                    Block::Meta => cur.saw_meta = true,
+                    Block::Command => cur.saw_command = true,
+                    Block::Stdout => cur.saw_stdout = true,
+                    Block::Stderr => cur.saw_stderr = true,
+                    // Optionale Blöcke zählen nicht zur Vollständigkeit
+                    Block::StdoutAnsi | Block::StderrAnsi | Block::Timing => {}
                 }
             }
-            continue;
+            // Ein Block endet; entweder geht's weiter mit nächstem Block,
+            // oder ein neuer Record beginnt (wieder mit META), oder Datei endet.
+            self.state = State::Idle;
+            return Ok(None);
         }
 
-        // Normale Zeilen: je nach Blockinhalt einsammeln
-        match state {
-            State::Idle => {
-                // Wenn wir bereits META/COMMAND/STDOUT/STDERR in diesem Record gesehen haben,
-                // und jetzt normale Zeilen kommen (Rauschen), ignoriere sie.
-                // Für Robustheit erlauben wir Kommentare/Zwischenzeilen außerhalb von Blöcken.
-                continue;
-            }
-            State::InBlock(Block::Meta) => {
+        // Normale Zeilen: je nach Blockinhalt einsammeln.
+        // Außerhalb von Blöcken (Kommentare, Zwischenzeilen) wird ignoriert.
+        let (State::InBlock(block, _), Some(cur)) = (self.state, self.current.as_mut()) else {
+            return Ok(None);
+        };
+        match block {
+            Block::Meta => {
                 // META ist key: value pro Zeile, leere Zeilen erlauben
                 if line.trim().is_empty() {
-                    continue;
+                    return Ok(None);
                 }
                 if let Some((k, v)) = line.split_once(':') {
-                    let key = k.trim().to_string();
-                    let value = v.trim().to_string();
-                    if let Some(m) = cur_meta.as_mut() {
-                        m.insert(key, value);
-                    }
-                } else {
-                    // Tolerant: ignoriere Zeilen ohne Doppelpunkt
-                    // (alternativ: bail!("invalid meta line: {line}"));
-                }
-            }
-            State::InBlock(Block::Command) => {
-                if !cur_cmd.is_empty() {
-                    cur_cmd.push('\n');
+                    cur.meta.push(k.trim(), v.trim());
+                } else if self.strict_meta {
+                    return Err(ParseError::InvalidMetaLine {
+                        line: line.to_string(),
+                        at: self.at,
+                    });
                 }
-                cur_cmd.push_str(line);
-            }
-            State::InBlock(Block::Stdout) => {
-                if !cur_stdout.is_empty() {
-                    cur_stdout.push('\n');
-                }
-                cur_stdout.push_str(line);
-            }
-            State::InBlock(Block::Stderr) => {
-                if !cur_stderr.is_empty() {
-                    cur_stderr.push('\n');
-                }
-                cur_stderr.push_str(line);
+                // Sonst tolerant: Zeilen ohne Doppelpunkt ignorieren
             }
+            Block::Command => push_line(&mut cur.cmd, &mut cur.has_line, line),
+            Block::Stdout => push_line(&mut cur.stdout, &mut cur.has_line, line),
+            Block::Stderr => push_line(&mut cur.stderr, &mut cur.has_line, line),
+            Block::StdoutAnsi => push_line(
+                cur.stdout_ansi.get_or_insert_with(String::new),
+                &mut cur.has_line,
+                line,
+            ),
+            Block::StderrAnsi => push_line(
+                cur.stderr_ansi.get_or_insert_with(String::new),
+                &mut cur.has_line,
+                line,
+            ),
+            Block::Timing => push_line(
+                cur.timing.get_or_insert_with(String::new),
+                &mut cur.has_line,
+                line,
+            ),
         }
+        Ok(None)
     }
+}
 
-    // Dateiende: Falls wir schon Blöcke gesehen haben, aber der Record nicht finalisiert wurde,
-    // handelt es sich um eine unvollständige Datei oder es fehlen <<<END>>> Marker.
-    // Wir prüfen, ob wir in einem offenen Block hängen:
-    match state {
-        State::InBlock(_) => {
-            bail!("unexpected EOF: block not closed with <<<END>>>");
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<RommyRecord, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
-        State::Idle => {
-            if cur_meta.is_some() {
-                finish_record(
-                    &mut cur_meta,
-                    &mut cur_cmd,
-                    &mut cur_stdout,
-                    &mut cur_stderr,
-                    saw_meta,
-                    saw_command,
-                    saw_stdout,
-                    saw_stderr,
-                    &mut out,
-                )?;
-            }
+        let item = self.next_record().transpose();
+        // Nach einem Fehler oder am Dateiende ist Schluss
+        if !matches!(item, Some(Ok(_))) {
+            self.done = true;
         }
+        item
     }
-
-    Ok(out)
 }
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use std::io::{self, Read, Write};
+use std::process::{Child, ExitStatus};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::timing::ChunkLog;
+use crate::{CYAN, RESET, YELLOW, ansi};
+
+const REFRESH: Duration = Duration::from_millis(200);
+
+#[derive(Default)]
+struct Progress {
+    stdout_bytes: usize,
+    stderr_bytes: usize,
+    last_line: String,
+}
+
+/// Capture a child without streaming, while a single status line on stderr shows
+/// elapsed time, bytes per stream and the last output line.
+/// Only call this when stderr is a terminal.
+/// Returns (stdout_bytes, stderr_bytes, exit_status)
+pub fn capture_with_status_line(
+    mut child: Child,
+    colors: bool,
+    timing: Option<Arc<ChunkLog>>,
+) -> Result<(Vec<u8>, Vec<u8>, ExitStatus)> {
+    fn reader<R: Read + Send + 'static>(
+        mut r: R,
+        progress: Arc<Mutex<Progress>>,
+        is_stderr: bool,
+        timing: Option<Arc<ChunkLog>>,
+    ) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            let mut all = Vec::new();
+            // Start of the line (or `\r` segment) still being written; only new
+            // bytes are scanned, so `\r`-only progress output stays linear
+            let mut line_start = 0;
+            loop {
+                match r.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if let Some(log) = &timing {
+                            log.record(is_stderr, n);
+                        }
+                        let scan_from = all.len().saturating_sub(1);
+                        all.extend_from_slice(&buf[..n]);
+                        // A break counts once something follows it; `\r\n` is one break
+                        for i in scan_from..all.len() - 1 {
+                            if all[i] == b'\n' || (all[i] == b'\r' && all[i + 1] != b'\n') {
+                                line_start = i + 1;
+                            }
+                        }
+                        let mut p = progress.lock().unwrap_or_else(|e| e.into_inner());
+                        if is_stderr {
+                            p.stderr_bytes += n;
+                        } else {
+                            p.stdout_bytes += n;
+                        }
+                        if let Some(line) = last_line(&all[line_start..]) {
+                            p.last_line = line;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            all
+        })
+    }
+
+    let progress = Arc::new(Mutex::new(Progress::default()));
+    let h_out = child
+        .stdout
+        .take()
+        .map(|r| reader(r, Arc::clone(&progress), false, timing.clone()));
+    let h_err = child
+        .stderr
+        .take()
+        .map(|r| reader(r, Arc::clone(&progress), true, timing));
+
+    let started = Instant::now();
+    let width = terminal_width();
+    let mut stderr = io::stderr();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let line = {
+            let p = progress.lock().unwrap_or_else(|e| e.into_inner());
+            status_line(started.elapsed(), &p, width)
+        };
+        if colors {
+            let _ = write!(stderr, "\r\x1b[K{CYAN}{line}{RESET}");
+        } else {
+            let _ = write!(stderr, "\r\x1b[K{line}");
+        }
+        let _ = stderr.flush();
+        thread::sleep(REFRESH);
+    };
+    // Clear the status line again before anything else is printed
+    let _ = write!(stderr, "\r\x1b[K");
+    let _ = stderr.flush();
+
+    let stdout_bytes = h_out
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
+    let stderr_bytes = h_err
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
+    Ok((stdout_bytes, stderr_bytes, status))
+}
+
+/// Print the last `n` lines of stderr and stdout, e.g. after a failed `--no-stream` run.
+pub fn print_failure_tail(stdout: &[u8], stderr: &[u8], n: usize, colors: bool) {
+    if n == 0 {
+        return;
+    }
+    for (name, bytes, color) in [("stderr", stderr, YELLOW), ("stdout", stdout, "")] {
+        let text = String::from_utf8_lossy(bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let tail = &lines[lines.len().saturating_sub(n)..];
+        crate::rommy_note_cyan(
+            colors,
+            &format!("--- last {} line(s) of {} ---", tail.len(), name),
+        );
+        for line in tail {
+            if colors && !color.is_empty() {
+                eprintln!("{color}{line}{RESET}");
+            } else {
+                eprintln!("{line}");
+            }
+        }
+    }
+}
+
+fn status_line(elapsed: Duration, p: &Progress, width: usize) -> String {
+    let head = format!(
+        "[rommy] {:.1}s | stdout {} | stderr {}",
+        elapsed.as_secs_f64(),
+        format_bytes(p.stdout_bytes),
+        format_bytes(p.stderr_bytes)
+    );
+    let mut line = if p.last_line.is_empty() {
+        head
+    } else {
+        format!("{head} | {}", p.last_line)
+    };
+    if line.chars().count() > width.saturating_sub(1) {
+        line = line.chars().take(width.saturating_sub(2)).collect();
+        line.push('…');
+    }
+    line
+}
+
+/// Last non-empty line of a chunk, as it would appear on a terminal (no escapes, after `\r`).
+fn last_line(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    text.lines()
+        .rev()
+        .map(|l| {
+            let visible = l.rsplit('\r').find(|s| !s.trim().is_empty()).unwrap_or("");
+            ansi::strip_str(visible)
+                .chars()
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+        })
+        .find(|l| !l.trim().is_empty())
+        .map(|l| l.trim().to_string())
+}
+
+/// Width of the terminal: asked from the one stderr is attached to,
+/// then `$COLUMNS` (rarely exported to child processes), then 80.
+pub fn terminal_width() -> usize {
+    stderr_columns()
+        .or_else(|| std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+        .filter(|&w: &usize| w > 20)
+        .unwrap_or(80)
+}
+
+#[cfg(unix)]
+fn stderr_columns() -> Option<usize> {
+    // SAFETY: winsize is plain data that TIOCGWINSZ fills in.
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) != 0 {
+            return None;
+        }
+        (size.ws_col > 0).then_some(size.ws_col as usize)
+    }
+}
+
+#[cfg(not(unix))]
+fn stderr_columns() -> Option<usize> {
+    None
+}
+
+pub fn format_bytes(n: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
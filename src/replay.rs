@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use rommy::parser::RommyRecord;
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{
+    AsciicastConfig, RESET, ReplayConfig, YELLOW, color_is_enabled, rommy_note_cyan, timing,
+};
+
+/// A piece of output as it appeared while the command ran.
+struct Event<'a> {
+    at_ms: u64,
+    stderr: bool,
+    text: &'a str,
+}
+
+/// Parse `--speed`: `2x`, `0.5x` or a plain factor.
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    let factor = s.trim().trim_end_matches(['x', 'X']);
+    match factor.parse::<f64>() {
+        Ok(f) if f > 0.0 && f.is_finite() => Ok(f),
+        _ => Err(format!("invalid speed '{s}' (expected e.g. 2x or 0.5x)")),
+    }
+}
+
+/// Record `record_number` of `path` (default: the last one), with its 1-based number.
+fn load(path: &Path, record_number: Option<usize>) -> Result<(usize, RommyRecord)> {
+    let mut records = rommy::parser::parse_file(path)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    anyhow::ensure!(
+        !records.is_empty(),
+        "No records found in {}",
+        path.display()
+    );
+    let n = record_number.unwrap_or(records.len());
+    anyhow::ensure!(n > 0, "--record must be >= 1");
+    anyhow::ensure!(
+        n <= records.len(),
+        "--record {} is out of range (1..={})",
+        n,
+        records.len()
+    );
+    Ok((n, records.swap_remove(n - 1)))
+}
+
+/// Cut the recorded output into timed events. The verbatim (`*_ANSI`) output is
+/// preferred so that colors survive; chunk sizes refer to STDOUT/STDERR and are
+/// moved over to it line by line.
+fn events(record: &RommyRecord) -> Option<Vec<Event<'_>>> {
+    let chunks = timing::parse(record.timing.as_deref()?);
+    let streams = [
+        (&record.stdout, record.stdout_ansi.as_deref()),
+        (&record.stderr, record.stderr_ansi.as_deref()),
+    ];
+
+    let mut pieces = Vec::with_capacity(2);
+    for (stream, (plain, verbatim)) in streams.into_iter().enumerate() {
+        let mut end = 0;
+        let ends: Vec<usize> = chunks
+            .iter()
+            .filter(|c| c.stderr as usize == stream)
+            .map(|c| {
+                end += c.len;
+                end
+            })
+            .collect();
+        let (text, ends) = match verbatim {
+            Some(verbatim) => (
+                verbatim,
+                timing::remap(plain.as_bytes(), verbatim.as_bytes(), &ends),
+            ),
+            None => (plain.as_str(), ends),
+        };
+        let mut prev = 0;
+        let lens = ends.into_iter().map(|end| {
+            let len = end.saturating_sub(prev);
+            prev = prev.max(end);
+            len
+        });
+        pieces.push(timing::split(text, lens).into_iter());
+    }
+
+    let mut events = Vec::with_capacity(chunks.len());
+    for c in &chunks {
+        if let Some(text) = pieces[c.stderr as usize].next() {
+            events.push(Event {
+                at_ms: c.at_ms,
+                stderr: c.stderr,
+                text,
+            });
+        }
+    }
+    Some(events)
+}
+
+fn no_timing(path: &Path, record_number: usize) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Record {} in {} has no chunk timing (record it with `rommy run --timing`)",
+        record_number,
+        path.display()
+    )
+}
+
+/// `rommy replay`: play a record's output back at the recorded pace.
+pub fn replay(cfg: ReplayConfig) -> Result<()> {
+    let colors = color_is_enabled(cfg.color);
+    let (record_number, record) = load(&cfg.path, cfg.record)?;
+    let events = events(&record).ok_or_else(|| no_timing(&cfg.path, record_number))?;
+
+    rommy_note_cyan(
+        colors,
+        &format!(
+            "Replaying record {} of {} at {}x",
+            record_number,
+            cfg.path.display(),
+            cfg.speed
+        ),
+    );
+    let started = Instant::now();
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+    for event in events {
+        let due = Duration::from_secs_f64(event.at_ms as f64 / 1000.0 / cfg.speed);
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+        if !event.stderr {
+            stdout.write_all(event.text.as_bytes())?;
+            stdout.flush()?;
+        } else if colors {
+            write!(stderr, "{YELLOW}{}{RESET}", event.text)?;
+        } else {
+            stderr.write_all(event.text.as_bytes())?;
+        }
+    }
+    // The stored output may end without a newline
+    stdout.flush()?;
+    Ok(())
+}
+
+/// `rommy export asciicast`: write a record as an asciicast v2 file.
+pub fn export_asciicast(cfg: AsciicastConfig) -> Result<()> {
+    let (record_number, record) = load(&cfg.path, cfg.record)?;
+    let events = events(&record).ok_or_else(|| no_timing(&cfg.path, record_number))?;
+
+    let mut header = json!({
+        "version": 2,
+        "width": cfg.cols,
+        "height": cfg.rows,
+    });
+    if let Some(start) = record
+        .meta
+        .get("start_ts")
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+    {
+        header["timestamp"] = json!(start.timestamp());
+    }
+    if let Some(ms) = record
+        .meta
+        .get("duration_ms")
+        .and_then(|ms| ms.parse::<u64>().ok())
+    {
+        header["duration"] = json!(ms as f64 / 1000.0);
+    }
+    if let Some(command) = record.meta.get("command_line") {
+        header["command"] = json!(command);
+    }
+    if let Some(label) = record.meta.get("label") {
+        header["title"] = json!(label);
+    }
+
+    let mut cast = format!("{header}\n");
+    for event in events {
+        // A terminal needs CR LF; stderr is shown the way `rommy run` streams it
+        let text = event.text.replace("\r\n", "\n").replace('\n', "\r\n");
+        let text = if event.stderr {
+            format!("{YELLOW}{text}{RESET}")
+        } else {
+            text
+        };
+        let _ = writeln!(cast, "{}", json!([event.at_ms as f64 / 1000.0, "o", text]));
+    }
+
+    match &cfg.out {
+        Some(out) => {
+            fs::write(out, cast).with_context(|| format!("Cannot write {}", out.display()))?
+        }
+        None => print!("{cast}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rommy::writer::RommyCommand;
+
+use crate::{AnsiMode, RerunConfig, RunConfig, diff, execute};
+
+/// Longest diff excerpt printed per stream in the summary
+const MAX_DIFF_LINES: usize = 40;
+
+/// Re-execute a recorded command with its recorded cwd and env, then summarise what changed.
+/// Env values come from the record if it was made with `--record-env`, else from the current
+/// environment.
+pub fn rerun(cfg: RerunConfig) -> Result<()> {
+    let records = rommy::parser::parse_file(&cfg.path)
+        .with_context(|| format!("failed to parse {}", cfg.path.display()))?;
+    anyhow::ensure!(
+        !records.is_empty(),
+        "No records found in {}",
+        cfg.path.display()
+    );
+    let record_number = cfg.record.unwrap_or(records.len());
+    anyhow::ensure!(record_number > 0, "--record must be >= 1");
+    anyhow::ensure!(
+        record_number <= records.len(),
+        "--record {} is out of range (1..={})",
+        record_number,
+        records.len()
+    );
+    let original = &records[record_number - 1];
+    let meta = &original.meta;
+
+    let mut script_file = None;
+    let display_command = if let Some(line) = meta.get("command_line") {
+        RommyCommand::Line(line.clone())
+    } else if let Some(script_path) = meta.get("script_path") {
+        // The COMMAND block holds the script as it ran; replay that content,
+        // not whatever the script file contains today.
+        let script_text = original
+            .command
+            .strip_prefix("#!/usr/bin/env bash\n")
+            .unwrap_or(&original.command);
+        // Run the file itself while it is unchanged, so `$0` still points next to it
+        let unchanged = fs::read_to_string(script_path)
+            .is_ok_and(|text| text.trim_end_matches('\n') == script_text.trim_end_matches('\n'));
+        if !unchanged {
+            eprintln!(
+                "WARN: {script_path} changed since the recorded run; running the recorded \
+                 script from a temporary copy, so $0 points there"
+            );
+            script_file = Some(write_rerun_script(script_text)?);
+        }
+        RommyCommand::Script {
+            path: PathBuf::from(script_path),
+            content: format!("{}\n", original.command),
+        }
+    } else if let Some(line) = original.command.strip_prefix("$ ") {
+        RommyCommand::Line(line.to_string())
+    } else {
+        anyhow::bail!(
+            "Record {} in {} has no command_line or script_path",
+            record_number,
+            cfg.path.display()
+        );
+    };
+
+    let mut keys: Vec<&str> = meta
+        .get("env_keys")
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Records from before `env_keys` carry only `env.KEY` entries
+    for (key, _) in meta.iter() {
+        if let Some(key) = key.strip_prefix("env.")
+            && !keys.contains(&key)
+        {
+            keys.push(key);
+        }
+    }
+    let mut envs = Vec::new();
+    for key in &keys {
+        match meta
+            .get(&format!("env.{key}"))
+            .cloned()
+            .or_else(|| std::env::var(key).ok())
+        {
+            Some(value) => envs.push(format!("{key}={value}")),
+            None => eprintln!("WARN: {key} was set for the original run but is not set now"),
+        }
+    }
+    let record_env = meta.iter().any(|(k, _)| k.starts_with("env."));
+
+    let meta_u64 = |key: &str| meta.get(key).and_then(|v| v.parse::<u64>().ok());
+    let run_cfg = RunConfig {
+        out: cfg.out.clone(),
+        cwd: meta.get("cwd").map(PathBuf::from),
+        envs,
+        record_env,
+        append: cfg.append,
+        label: cfg.label.clone().or_else(|| meta.get("label").cloned()),
+        no_stream: cfg.no_stream,
+        tail_lines: cfg.tail_lines,
+        color: cfg.color,
+        ansi: match meta.get("ansi").map(String::as_str) {
+            Some("strip") => AnsiMode::Strip,
+            Some("both") => AnsiMode::Both,
+            _ => AnsiMode::Keep,
+        },
+        limit_mem: meta_u64("limit_mem_bytes"),
+        limit_cpu: meta_u64("limit_cpu_secs"),
+        limit_fsize: meta_u64("limit_fsize_bytes"),
+        limit_nproc: meta_u64("limit_nproc"),
+        script: None,
+        cmd: Vec::new(),
+        no_normalize: cfg.no_normalize,
+        compress: None,
+        detach: false,
+        copy: false,
+        timing: original.timing.is_some(),
+        detached_id: None,
+        script_file: script_file.clone(),
+    };
+
+    let source = fs::canonicalize(&cfg.path).unwrap_or_else(|_| cfg.path.clone());
+    let rerun_of = format!("{}#{}", source.display(), record_number);
+    let outcome = execute(
+        run_cfg,
+        display_command,
+        vec![("rerun_of".to_string(), rerun_of.clone())],
+    );
+    if let Some(path) = &script_file {
+        let _ = fs::remove_file(path);
+    }
+    let outcome = outcome?;
+
+    // Summary
+    println!("Rerun of {} -> {}", rerun_of, outcome.out_path.display());
+    let old_code = meta.get("exit_code").map(String::as_str).unwrap_or("?");
+    let code_note = if old_code == outcome.exit_code.to_string() {
+        "unchanged"
+    } else {
+        "changed"
+    };
+    println!(
+        "  exit code: {} -> {} ({})",
+        old_code, outcome.exit_code, code_note
+    );
+    match meta.get("duration_ms").and_then(|v| v.parse::<i64>().ok()) {
+        Some(old_ms) => println!(
+            "  duration:  {} ms -> {} ms ({:+} ms)",
+            old_ms,
+            outcome.duration_ms,
+            outcome.duration_ms - old_ms
+        ),
+        None => println!("  duration:  ? -> {} ms", outcome.duration_ms),
+    }
+
+    let new_stdout = String::from_utf8_lossy(&outcome.stdout);
+    let new_stderr = String::from_utf8_lossy(&outcome.stderr);
+    let mut diffs = Vec::new();
+    for (name, old, new) in [
+        (
+            "stdout",
+            original.stdout.as_str(),
+            new_stdout.trim_end_matches('\n'),
+        ),
+        (
+            "stderr",
+            original.stderr.as_str(),
+            new_stderr.trim_end_matches('\n'),
+        ),
+    ] {
+        let (inserted, deleted) = diff::line_changes(old, new);
+        if inserted == 0 && deleted == 0 {
+            println!("  {name}:    identical");
+        } else {
+            println!("  {name}:    +{inserted} -{deleted} line(s)");
+            if let Some(d) = diff::unified(old, new, &format!("a/{name}"), &format!("b/{name}")) {
+                diffs.push(d);
+            }
+        }
+    }
+    for d in diffs {
+        let lines: Vec<&str> = d.lines().collect();
+        for line in lines.iter().take(MAX_DIFF_LINES) {
+            println!("{line}");
+        }
+        if lines.len() > MAX_DIFF_LINES {
+            println!("... ({} more diff line(s))", lines.len() - MAX_DIFF_LINES);
+        }
+    }
+    Ok(())
+}
+
+/// Materialise a recorded script under {temp_dir}/rommy/rerun-{ts}-{pid}.sh
+fn write_rerun_script(script_text: &str) -> Result<PathBuf> {
+    let mut dir = std::env::temp_dir();
+    dir.push("rommy");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create scratch dir {}", dir.display()))?;
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let path = dir.join(format!("rerun-{ts}-{}.sh", std::process::id()));
+    fs::write(&path, script_text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
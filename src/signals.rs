@@ -0,0 +1,64 @@
+use std::process::ExitStatus;
+
+/// Signal that terminated the process, if any (always `None` off Unix).
+pub fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Human-readable name for a signal number, e.g. 15 -> "SIGTERM".
+pub fn signal_name(sig: i32) -> String {
+    #[cfg(unix)]
+    {
+        let name = match sig {
+            libc::SIGHUP => "SIGHUP",
+            libc::SIGINT => "SIGINT",
+            libc::SIGQUIT => "SIGQUIT",
+            libc::SIGILL => "SIGILL",
+            libc::SIGABRT => "SIGABRT",
+            libc::SIGBUS => "SIGBUS",
+            libc::SIGFPE => "SIGFPE",
+            libc::SIGKILL => "SIGKILL",
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGPIPE => "SIGPIPE",
+            libc::SIGALRM => "SIGALRM",
+            libc::SIGTERM => "SIGTERM",
+            libc::SIGXCPU => "SIGXCPU",
+            libc::SIGXFSZ => "SIGXFSZ",
+            _ => return format!("SIG{sig}"),
+        };
+        name.to_string()
+    }
+    #[cfg(not(unix))]
+    {
+        format!("SIG{sig}")
+    }
+}
+
+/// Is a process with this PID still alive?
+#[cfg(unix)]
+pub fn pid_alive(pid: u32) -> bool {
+    // SAFETY: kill with signal 0 only performs the permission/existence check.
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Send `sig` to every process in the process group `pgid`.
+#[cfg(unix)]
+pub fn signal_group(pgid: u32, sig: i32) -> std::io::Result<()> {
+    // SAFETY: plain syscall; a negative pid addresses the process group.
+    let rc = unsafe { libc::kill(-(pgid as libc::pid_t), sig) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use rommy::meta::{MetaError, Status};
+use rommy::parser::RommyRecord;
+use serde_json::json;
+
+/// Keys every record written by `rommy run` carries. An imported record lists
+/// the keys its source could not tell in `unknown_fields`.
+const REQUIRED_KEYS: &[&str] = &[
+    "rommy_version",
+    "cwd",
+    "start_ts",
+    "end_ts",
+    "duration_ms",
+    "status",
+    "exit_code",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// One finding of `validate --strict`.
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub level: Level,
+    /// Stable identifier of the check, e.g. `duration-mismatch`
+    pub code: &'static str,
+    /// 1-based record number within the file
+    pub record: usize,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "level": self.level.as_str(),
+            "code": self.code,
+            "record": self.record,
+            "message": self.message,
+        })
+    }
+}
+
+/// Semantic checks over the records of one file, fed in file order.
+#[derive(Default)]
+pub struct Checker {
+    previous_start: Option<(usize, DateTime<Utc>)>,
+    pub issues: Vec<Issue>,
+}
+
+impl Checker {
+    fn push(&mut self, level: Level, code: &'static str, record: usize, message: String) {
+        self.issues.push(Issue {
+            level,
+            code,
+            record,
+            message,
+        });
+    }
+
+    /// A typed META value, or `None` after reporting a malformed one as `code`.
+    fn value<T>(
+        &mut self,
+        code: &'static str,
+        n: usize,
+        value: Result<Option<T>, MetaError>,
+    ) -> Option<T> {
+        value.unwrap_or_else(|err| {
+            self.push(Level::Error, code, n, err.to_string());
+            None
+        })
+    }
+
+    /// Check record `n`.
+    pub fn check(&mut self, n: usize, record: &RommyRecord) {
+        let meta = &record.meta;
+        let unknown: Vec<&str> = meta
+            .get("unknown_fields")
+            .map(|v| v.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+
+        for key in REQUIRED_KEYS {
+            if !meta.contains_key(key) && !unknown.contains(key) {
+                self.push(
+                    Level::Error,
+                    "missing-key",
+                    n,
+                    format!("META key `{key}` is missing"),
+                );
+            }
+        }
+        if !meta.contains_key("command_line")
+            && !meta.contains_key("script_path")
+            && !unknown.contains(&"command_line")
+        {
+            self.push(
+                Level::Error,
+                "missing-key",
+                n,
+                "META has neither `command_line` nor `script_path`".to_string(),
+            );
+        }
+        for key in meta.duplicates() {
+            self.push(
+                Level::Warning,
+                "duplicate-key",
+                n,
+                format!("META key `{key}` appears more than once; the last value is used"),
+            );
+        }
+
+        let start = self.value("invalid-timestamp", n, record.start());
+        let end = self.value("invalid-timestamp", n, record.end());
+        let duration = self
+            .value("invalid-duration", n, record.duration())
+            .map(|d| d.as_millis() as i64);
+        if let (Some(start), Some(end)) = (start, end) {
+            let elapsed = (end - start).num_milliseconds();
+            if elapsed < 0 {
+                self.push(
+                    Level::Error,
+                    "end-before-start",
+                    n,
+                    format!("`end_ts` is {} ms before `start_ts`", -elapsed),
+                );
+            } else if let Some(ms) = duration
+                && (ms - elapsed).abs() > 1
+            {
+                self.push(
+                    Level::Warning,
+                    "duration-mismatch",
+                    n,
+                    format!("`duration_ms` is {ms}, but end_ts - start_ts is {elapsed} ms"),
+                );
+            }
+        }
+        if let Some(start) = start {
+            if let Some((previous, previous_start)) = self.previous_start
+                && start < previous_start
+            {
+                self.push(
+                    Level::Warning,
+                    "out-of-order",
+                    n,
+                    format!("record starts before record {previous}"),
+                );
+            }
+            self.previous_start = Some((n, start));
+        }
+
+        let exit_code = self.value("invalid-exit-code", n, record.exit_code());
+        if let Some(status) = meta.get("status") {
+            if record.status().is_err() {
+                self.push(
+                    Level::Warning,
+                    "unknown-status",
+                    n,
+                    format!("unknown `status` {status:?}"),
+                );
+            }
+            if let Some(code) = exit_code
+                && (status == Status::Ok.as_str()) != (code == 0)
+            {
+                self.push(
+                    Level::Error,
+                    "status-mismatch",
+                    n,
+                    format!("`status` is {status:?}, but `exit_code` is {code}"),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// One read from the child's stdout or stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Milliseconds since the command started
+    pub at_ms: u64,
+    pub stderr: bool,
+    pub len: usize,
+}
+
+/// Collects chunk timing while a command runs (`run --timing`).
+pub struct ChunkLog {
+    start: Instant,
+    chunks: Mutex<Vec<Chunk>>,
+}
+
+impl ChunkLog {
+    pub fn new() -> Self {
+        ChunkLog {
+            start: Instant::now(),
+            chunks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, stderr: bool, len: usize) {
+        let at_ms = self.start.elapsed().as_millis() as u64;
+        let mut chunks = self.chunks.lock().unwrap_or_else(|e| e.into_inner());
+        chunks.push(Chunk { at_ms, stderr, len });
+    }
+
+    /// Render the TIMING block. Chunk sizes refer to the stored streams, which
+    /// normalising or ANSI stripping may have changed compared to the `raw`
+    /// capture; see [`remap`].
+    pub fn to_block(&self, raw: [&[u8]; 2], stored: [&[u8]; 2]) -> String {
+        let mut chunks = self
+            .chunks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        chunks.sort_by_key(|c| c.at_ms);
+
+        let mut lens: [Vec<usize>; 2] = Default::default();
+        for (stream, lens) in lens.iter_mut().enumerate() {
+            let mut end = 0;
+            let raw_ends: Vec<usize> = chunks
+                .iter()
+                .filter(|c| c.stderr as usize == stream)
+                .map(|c| {
+                    end += c.len;
+                    end
+                })
+                .collect();
+            let mut prev = 0;
+            for end in remap(raw[stream], stored[stream], &raw_ends) {
+                lens.push(end - prev);
+                prev = end;
+            }
+        }
+
+        let mut next = [lens[0].iter(), lens[1].iter()];
+        let mut out = String::new();
+        for c in &chunks {
+            let len = next[c.stderr as usize].next().copied().unwrap_or(0);
+            if len > 0 {
+                let _ = writeln!(
+                    out,
+                    "{} {} {}",
+                    c.at_ms,
+                    if c.stderr { 'e' } else { 'o' },
+                    len
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Move chunk end offsets in `from` to the matching offsets in `to`. Identical
+/// texts keep them as they are; otherwise a chunk ends after as many complete
+/// lines in `to` as it did in `from`, so a partial line goes with the chunk
+/// that completes it.
+pub fn remap(from: &[u8], to: &[u8], ends: &[usize]) -> Vec<usize> {
+    if from == to {
+        return ends.iter().map(|&e| e.min(to.len())).collect();
+    }
+    let line_ends: Vec<usize> = to
+        .iter()
+        .enumerate()
+        .filter(|&(_, &b)| b == b'\n')
+        .map(|(i, _)| i + 1)
+        .collect();
+    // Chunk ends only grow, so the line count is carried forward from one to the next
+    let (mut counted_to, mut lines) = (0, 0);
+    ends.iter()
+        .map(|&end| {
+            if end >= from.len() {
+                return to.len();
+            }
+            if end < counted_to {
+                (counted_to, lines) = (0, 0);
+            }
+            lines += from[counted_to..end]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+            counted_to = end;
+            match lines {
+                0 => 0,
+                n => line_ends.get(n - 1).copied().unwrap_or(to.len()),
+            }
+        })
+        .collect()
+}
+
+/// Parse a TIMING block; malformed lines are skipped.
+pub fn parse(block: &str) -> Vec<Chunk> {
+    block
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let at_ms = fields.next()?.parse().ok()?;
+            let stderr = match fields.next()? {
+                "o" => false,
+                "e" => true,
+                _ => return None,
+            };
+            let len = fields.next()?.parse().ok()?;
+            Some(Chunk { at_ms, stderr, len })
+        })
+        .collect()
+}
+
+/// Split `text` into consecutive pieces of the given byte lengths, moved forward
+/// to the next character boundary where needed. Whatever is left over is
+/// appended to the last piece.
+pub fn split(text: &str, lens: impl Iterator<Item = usize>) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut pos = 0;
+    for len in lens {
+        let mut end = (pos + len).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        pieces.push(&text[pos..end]);
+        pos = end;
+    }
+    if pos < text.len() {
+        match pieces.last_mut() {
+            Some(last) => {
+                let start = pos - last.len();
+                *last = &text[start..];
+            }
+            None => pieces.push(&text[pos..]),
+        }
+    }
+    pieces
+}
@@ -0,0 +1,417 @@
+//! Writing `.rommy` records.
+//!
+//! [`RecordBuilder`] assembles a [`RommyRecord`] with the same META layout as
+//! `rommy run`, [`write_record`] serialises it, and [`append_record`] adds it to
+//! a file under the same lock the CLI takes, so records written by other tools
+//! can share files with `rommy run --append`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::compression::Compression;
+use crate::meta::Meta;
+use crate::parser::{FORMAT_VERSION, RommyRecord};
+
+/// META keys that lead a built record, in this order.
+const META_HEAD: &[&str] = &[
+    "rommy_version",
+    "format_version",
+    "label",
+    "cwd",
+    "user",
+    "host",
+    "script_path",
+    "command_line",
+    "start_ts",
+    "end_ts",
+    "duration_ms",
+    "output_path",
+];
+
+/// META keys that close a built record, after all others (sorted by name).
+const META_TAIL: &[&str] = &["status", "exit_code", "unknown_fields"];
+
+/// What a record ran: a bash line or a script file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RommyCommand {
+    Line(String),
+    Script { path: PathBuf, content: String },
+}
+
+impl RommyCommand {
+    /// One-line description, e.g. for `rommy status`
+    pub fn summary(&self) -> String {
+        match self {
+            RommyCommand::Line(line) => line.clone(),
+            RommyCommand::Script { path, .. } => format!("script {}", path.display()),
+        }
+    }
+}
+
+/// Builds a [`RommyRecord`] with the META keys `rommy run` writes.
+///
+/// ```
+/// use rommy::writer::{RecordBuilder, RommyCommand};
+///
+/// let record = RecordBuilder::new(&RommyCommand::Line("cargo test".into()))
+///     .label("ci")
+///     .exit(0, "ok")
+///     .stdout("test result: ok\n")
+///     .build();
+/// assert_eq!(record.command, "$ cargo test");
+/// assert_eq!(record.meta["status"], "ok");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecordBuilder {
+    meta: Vec<(String, String)>,
+    command: String,
+    stdout: String,
+    stderr: String,
+    stdout_ansi: Option<String>,
+    stderr_ansi: Option<String>,
+    timing: Option<String>,
+}
+
+/// Block content as the parser returns it: the final line break belongs to the block end.
+fn block_text(text: impl Into<String>) -> String {
+    let mut text = text.into();
+    if text.ends_with('\n') {
+        text.pop();
+    }
+    text
+}
+
+impl RecordBuilder {
+    /// A record of `command`, stamped with this crate's version.
+    pub fn new(command: &RommyCommand) -> Self {
+        let (key, value, text) = match command {
+            RommyCommand::Line(line) => ("command_line", line.clone(), format!("$ {line}")),
+            RommyCommand::Script { path, content } => (
+                "script_path",
+                path.display().to_string(),
+                block_text(content.as_str()),
+            ),
+        };
+        let mut builder = Self::without_command().meta(key, value);
+        builder.command = text;
+        builder
+    }
+
+    /// A record whose command is not known (e.g. an imported plain log):
+    /// empty COMMAND block, no `command_line` or `script_path`.
+    pub fn without_command() -> Self {
+        RecordBuilder {
+            meta: vec![
+                (
+                    "rommy_version".to_string(),
+                    env!("CARGO_PKG_VERSION").to_string(),
+                ),
+                ("format_version".to_string(), FORMAT_VERSION.to_string()),
+            ],
+            command: String::new(),
+            stdout: String::new(),
+            stderr: String::new(),
+            stdout_ansi: None,
+            stderr_ansi: None,
+            timing: None,
+        }
+    }
+
+    /// Set a META key; a later value for the same key replaces the earlier one.
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn label(self, label: impl Into<String>) -> Self {
+        self.meta("label", label)
+    }
+
+    pub fn cwd(self, cwd: &Path) -> Self {
+        self.meta("cwd", cwd.display().to_string())
+    }
+
+    pub fn user(self, user: impl Into<String>) -> Self {
+        self.meta("user", user)
+    }
+
+    pub fn host(self, host: impl Into<String>) -> Self {
+        self.meta("host", host)
+    }
+
+    /// `start_ts`, `end_ts` and the `duration_ms` between them.
+    pub fn times(self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.meta("start_ts", start.to_rfc3339())
+            .meta("end_ts", end.to_rfc3339())
+            .meta("duration_ms", (end - start).num_milliseconds().to_string())
+    }
+
+    /// The file the record is written to, as `rommy run` notes it.
+    pub fn output_path(self, path: &Path) -> Self {
+        self.meta("output_path", path.display().to_string())
+    }
+
+    /// Exit code and status (`ok`, `error`, `killed`, ...).
+    pub fn exit(self, exit_code: i32, status: impl Into<String>) -> Self {
+        self.meta("status", status)
+            .meta("exit_code", exit_code.to_string())
+    }
+
+    pub fn stdout(mut self, text: impl Into<String>) -> Self {
+        self.stdout = block_text(text);
+        self
+    }
+
+    pub fn stderr(mut self, text: impl Into<String>) -> Self {
+        self.stderr = block_text(text);
+        self
+    }
+
+    /// Verbatim stdout with ANSI escapes, next to the plain STDOUT (`--ansi both`).
+    pub fn stdout_ansi(mut self, text: impl Into<String>) -> Self {
+        self.stdout_ansi = Some(block_text(text));
+        self
+    }
+
+    /// Verbatim stderr with ANSI escapes, next to the plain STDERR (`--ansi both`).
+    pub fn stderr_ansi(mut self, text: impl Into<String>) -> Self {
+        self.stderr_ansi = Some(block_text(text));
+        self
+    }
+
+    /// Chunk timing, one `<ms> <o|e> <bytes>` line per chunk (`--timing`).
+    pub fn timing(mut self, timing: impl Into<String>) -> Self {
+        self.timing = Some(block_text(timing));
+        self
+    }
+
+    /// The record, with META in the layout `rommy run` writes.
+    pub fn build(self) -> RommyRecord {
+        let mut meta = Meta::new();
+        for (key, value) in self.meta {
+            meta.insert(key, value);
+        }
+        let rank = |key: &str| {
+            if let Some(i) = META_HEAD.iter().position(|k| *k == key) {
+                (0, i)
+            } else if let Some(i) = META_TAIL.iter().position(|k| *k == key) {
+                (2, i)
+            } else {
+                (1, 0)
+            }
+        };
+        let mut entries: Vec<(&str, &str)> = meta.iter().collect();
+        entries.sort_by(|a, b| rank(a.0).cmp(&rank(b.0)).then_with(|| a.0.cmp(b.0)));
+        RommyRecord {
+            meta: entries.into_iter().collect(),
+            command: self.command,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            stdout_ansi: self.stdout_ansi,
+            stderr_ansi: self.stderr_ansi,
+            timing: self.timing,
+            source_version: FORMAT_VERSION,
+        }
+    }
+}
+
+/// Write one block; non-empty content gets its final line break back.
+fn write_block(f: &mut impl Write, marker: &str, text: &str) -> io::Result<()> {
+    writeln!(f, "<<<{marker}>>>")?;
+    if !text.is_empty() {
+        writeln!(f, "{text}")?;
+    }
+    writeln!(f, "<<<END>>>")
+}
+
+/// Serialise `record`, META in its own order. Reading the output back with the
+/// parser gives the same record, with two exceptions: META keys and values are
+/// trimmed, and a `\r` at the end of a line is taken as part of a CRLF break.
+///
+/// Blocks are text: `rommy run` stores captured output that is not valid UTF-8
+/// with U+FFFD in place of the invalid bytes, so such a record does not match
+/// what the command printed byte for byte.
+pub fn write_record(f: &mut impl Write, record: &RommyRecord) -> io::Result<()> {
+    writeln!(f, "<<<META>>>")?;
+    for (key, value) in record.meta.iter() {
+        writeln!(f, "{key}: {value}")?;
+    }
+    writeln!(f, "<<<END>>>")?;
+    write_block(f, "COMMAND", &record.command)?;
+    write_block(f, "STDOUT", &record.stdout)?;
+    write_block(f, "STDERR", &record.stderr)?;
+    if let Some(text) = &record.stdout_ansi {
+        write_block(f, "STDOUT_ANSI", text)?;
+    }
+    if let Some(text) = &record.stderr_ansi {
+        write_block(f, "STDERR_ANSI", text)?;
+    }
+    if let Some(text) = &record.timing {
+        write_block(f, "TIMING", text)?;
+    }
+    Ok(())
+}
+
+/// Sibling temp file used to replace `path` atomically.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let pid = std::process::id();
+    let base = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("rommy.rommy");
+    let mut tmp = path.to_path_buf();
+    tmp.set_file_name(format!(".{base}.{pid}.{nanos}.tmp"));
+    tmp
+}
+
+/// Lock file guarding `path`, e.g. `.build.rommy.lock`.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.to_path_buf();
+    let base = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("rommy.rommy");
+    lock.set_file_name(format!(".{base}.lock"));
+    lock
+}
+
+/// Exclusive lock on a record file, released when dropped.
+#[derive(Debug)]
+pub struct RecordLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl RecordLock {
+    /// Block until no other writer holds the lock for `path`.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let lock_path = lock_path(path);
+        loop {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(&lock_path)
+                .with_context(|| format!("Cannot open lock file {}", lock_path.display()))?;
+            file.lock_exclusive()
+                .with_context(|| format!("Cannot acquire lock {}", lock_path.display()))?;
+            // The holder we waited for may have removed the lock file; then this
+            // lock guards nothing and the current file has to be locked instead
+            if is_current(&file, &lock_path) {
+                return Ok(RecordLock {
+                    _file: file,
+                    path: lock_path,
+                });
+            }
+        }
+    }
+
+    /// Remove the lock file, for files that go away, and release the lock.
+    /// Waiting writers notice the removal and lock a fresh file.
+    pub fn remove(self) {
+        let _ = fs::remove_file(&self.path);
+        drop(self);
+    }
+}
+
+/// Whether `file` is still the lock file at `path`.
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(held), Ok(current)) => held.dev() == current.dev() && held.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Open files cannot be removed on other platforms, so the lock file is the current one.
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> bool {
+    true
+}
+
+/// Replace `path` atomically with `bytes`, or with its current content followed
+/// by `bytes` when `append` is set. Callers hold the [`RecordLock`] for `path`.
+pub fn replace_file(path: &Path, bytes: &[u8], append: bool, _lock: &RecordLock) -> Result<()> {
+    let tmp_path = temp_path(path);
+    let write_result = (|| -> Result<()> {
+        let mut temp = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&tmp_path)
+            .with_context(|| format!("Cannot create {}", tmp_path.display()))?;
+
+        if append {
+            // Compressed files stay as they are; new content follows as another frame.
+            match File::open(path) {
+                Ok(mut current) => {
+                    io::copy(&mut current, &mut temp).with_context(|| {
+                        format!("Cannot copy existing content from {}", path.display())
+                    })?;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Cannot open {}", path.display()));
+                }
+            }
+        }
+
+        temp.write_all(bytes)
+            .with_context(|| format!("Cannot write {}", tmp_path.display()))?;
+        temp.sync_all()
+            .with_context(|| format!("Cannot sync {}", tmp_path.display()))?;
+
+        #[cfg(windows)]
+        if path.exists() {
+            fs::remove_file(path).with_context(|| format!("Cannot replace {}", path.display()))?;
+        }
+
+        fs::rename(&tmp_path, path).with_context(|| {
+            format!(
+                "Cannot atomically move {} to {}",
+                tmp_path.display(),
+                path.display()
+            )
+        })?;
+        Ok(())
+    })();
+
+    if write_result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    write_result
+}
+
+/// Create the parent directory of `path` if needed.
+pub fn create_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create directory {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+/// Append `record` to `path` (created if missing) under the file's lock.
+/// A `.zst`/`.gz` path gets the record as a new compressed frame.
+pub fn append_record<P: AsRef<Path>>(path: P, record: &RommyRecord) -> Result<()> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    write_record(&mut bytes, record)?;
+    let bytes = Compression::from_path(path).encode(&bytes)?;
+    create_parent_dir(path)?;
+    let lock = RecordLock::acquire(path)?;
+    replace_file(path, &bytes, true, &lock)
+}
@@ -0,0 +1,85 @@
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn ansi_strip_removes_escape_codes_from_capture() {
+    let out_path = "target/tmp/ansi_strip.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--ansi",
+            "strip",
+            "--out",
+            out_path,
+            "--",
+            "printf",
+            "\\033[31mred\\033[0m plain\\n",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    assert_eq!(recs[0].stdout, "red plain");
+    assert_eq!(recs[0].stdout_ansi, None);
+    assert_eq!(recs[0].meta.get("ansi").map(String::as_str), Some("strip"));
+}
+
+#[test]
+fn ansi_both_keeps_verbatim_copy_and_show_can_render_or_strip() {
+    let out_path = "target/tmp/ansi_both.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--ansi",
+            "both",
+            "--out",
+            out_path,
+            "--",
+            "printf",
+            "\\033[32mgreen\\033[0m\\n",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    assert_eq!(recs[0].stdout, "green");
+    assert_eq!(recs[0].stdout_ansi.as_deref(), Some("\x1b[32mgreen\x1b[0m"));
+    assert_eq!(recs[0].stderr_ansi, None);
+
+    let rendered = Command::new(bin)
+        .args(["show", "--ansi", "render", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(rendered.status.success(), "show should succeed");
+    let stdout = String::from_utf8_lossy(&rendered.stdout);
+    assert!(
+        stdout.contains("\x1b[32mgreen"),
+        "render should emit stored colors, got: {stdout:?}"
+    );
+
+    // Piped output defaults to stripping
+    let stripped = Command::new(bin)
+        .args(["show", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(stripped.status.success(), "show should succeed");
+    let stdout = String::from_utf8_lossy(&stripped.stdout);
+    assert!(stdout.contains("green"), "unexpected output: {stdout:?}");
+    assert!(
+        !stdout.contains('\x1b'),
+        "strip should remove escapes, got: {stdout:?}"
+    );
+}
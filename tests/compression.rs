@@ -0,0 +1,82 @@
+use rommy::compression::Compression;
+use rommy::parser::parse_file;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn compressed_output_supports_append_and_transparent_reading() {
+    let out_path = "target/tmp/compressed.rommy.zst";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for (append, word) in [(false, "first"), (true, "second")] {
+        let mut cmd = Command::new(bin);
+        cmd.args(["run", "--no-stream", "--out", out_path]);
+        if append {
+            cmd.arg("--append");
+        }
+        let status = cmd
+            .args(["--", "echo", word])
+            .status()
+            .expect("failed to execute rommy run");
+        assert!(status.success(), "rommy run should succeed");
+    }
+
+    let bytes = fs::read(out_path).expect("output should exist");
+    assert_eq!(Compression::detect(&bytes), Compression::Zstd);
+
+    let recs = parse_file(out_path).expect("compressed file should parse");
+    assert_eq!(recs.len(), 2);
+    assert_eq!(recs[1].stdout, "second");
+
+    let output = Command::new(bin)
+        .args(["validate", out_path])
+        .output()
+        .expect("failed to execute rommy validate");
+    assert!(output.status.success(), "validate should read .rommy.zst");
+}
+
+#[test]
+fn compress_converts_plain_files_in_place() {
+    let dir = "target/tmp/compress_store";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create store dir");
+    let plain = format!("{dir}/run.rommy");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--out",
+            &plain,
+            "--",
+            "seq",
+            "1",
+            "300",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+    let before = parse_file(&plain).expect("plain file should parse");
+    let plain_size = fs::metadata(&plain).expect("plain file should exist").len();
+
+    let output = Command::new(bin)
+        .args(["compress", dir, "--codec", "gzip"])
+        .output()
+        .expect("failed to execute rommy compress");
+    assert!(output.status.success(), "compress should succeed");
+
+    let gz = format!("{plain}.gz");
+    assert!(!Path::new(&plain).exists(), "original should be replaced");
+    let after = parse_file(&gz).expect("compressed file should parse");
+    assert_eq!(before, after);
+    assert!(
+        fs::metadata(&gz)
+            .expect("compressed file should exist")
+            .len()
+            < plain_size
+    );
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::process::Command;
+
+const RECORD: &str = "<<<META>>>\nexit_code: 0\n<<<END>>>\n<<<COMMAND>>>\n$ echo hi\n<<<END>>>\n<<<STDOUT>>>\nhi\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+
+#[test]
+fn copy_raw_record_emits_osc52_sequence() {
+    let path = "target/tmp/copy_raw.rommy";
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    fs::write(path, format!("{RECORD}{}", RECORD.replace("hi", "bye")))
+        .expect("failed to write fixture");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .env_remove("TMUX")
+        .args([
+            "copy", path, "--record", "1", "--format", "raw", "--print", "--color", "never",
+        ])
+        .output()
+        .expect("failed to execute rommy copy");
+    assert!(
+        output.status.success(),
+        "rommy copy should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "\x1b]52;c;PDw8TUVUQT4+PgpleGl0X2NvZGU6IDAKPDw8RU5EPj4+Cjw8PENPTU1BTkQ+Pj4KJCBlY2hvIGhpCjw8PEVORD4+Pgo8PDxTVERPVVQ+Pj4KaGkKPDw8RU5EPj4+Cjw8PFNUREVSUj4+Pgo8PDxFTkQ+Pj4K\x07"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Copied record 1 of"), "stderr: {stderr}");
+}
+
+#[test]
+fn copy_defaults_to_most_recent_record_in_store() {
+    let root = "target/tmp/copy_root";
+    let _ = fs::remove_dir_all(root);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for word in ["older", "newer"] {
+        let status = Command::new(bin)
+            .env("ROMMY_ROOT", root)
+            .args(["run", "--no-stream", "--", "echo", word])
+            .status()
+            .expect("failed to execute rommy run");
+        assert!(status.success(), "rommy run should succeed");
+    }
+
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .env("TMUX", "/tmp/tmux-0/default,1,0")
+        .args(["copy", "--print"])
+        .output()
+        .expect("failed to execute rommy copy");
+    assert!(output.status.success(), "rommy copy should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("\x1bPtmux;\x1b\x1b]52;c;") && stdout.ends_with("\x07\x1b\\"),
+        "expected a tmux passthrough sequence: {stdout:?}"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("newer"), "stderr: {stderr}");
+}
@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn detach_attach_follows_output_until_record_is_written() {
+    let root = "target/tmp/detach_attach_root";
+    let _ = fs::remove_dir_all(root);
+    let out_path = "target/tmp/detach_attach.rommy";
+    let _ = fs::remove_file(out_path);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let started = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args([
+            "run",
+            "--detach",
+            "--out",
+            out_path,
+            "--",
+            "bash",
+            "-c",
+            "echo tick-1; sleep 1; echo tick-2",
+        ])
+        .output()
+        .expect("failed to execute rommy run --detach");
+    assert!(started.status.success(), "detach should succeed");
+    let id = String::from_utf8_lossy(&started.stdout).trim().to_string();
+    assert!(!id.is_empty(), "detach should print the run id");
+
+    let status = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["status"])
+        .output()
+        .expect("failed to execute rommy status");
+    let listing = String::from_utf8_lossy(&status.stdout);
+    assert!(listing.contains(&id), "status should list {id}: {listing}");
+
+    let attach = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["attach", &id])
+        .output()
+        .expect("failed to execute rommy attach");
+    assert!(attach.status.success(), "attach should succeed");
+    let followed = String::from_utf8_lossy(&attach.stdout);
+    assert!(
+        followed.contains("tick-1") && followed.contains("tick-2"),
+        "attach should follow live output, got: {followed}"
+    );
+
+    let recs = parse_file(out_path).expect("record should be written after attach returns");
+    assert_eq!(recs[0].meta.get("status").map(String::as_str), Some("ok"));
+    assert!(recs[0].stdout.contains("tick-2"));
+    let leftovers: Vec<_> = fs::read_dir(format!("{root}/runs"))
+        .expect("runs directory should exist")
+        .map(|entry| entry.expect("readable entry").file_name())
+        .collect();
+    assert!(
+        leftovers.is_empty(),
+        "live log not cleaned up: {leftovers:?}"
+    );
+}
+
+#[test]
+fn stop_signals_detached_run_and_finalises_record() {
+    let root = "target/tmp/detach_stop_root";
+    let _ = fs::remove_dir_all(root);
+    let out_path = "target/tmp/detach_stop.rommy";
+    let _ = fs::remove_file(out_path);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let started = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["run", "--detach", "--out", out_path, "--", "sleep", "30"])
+        .output()
+        .expect("failed to execute rommy run --detach");
+    assert!(started.status.success(), "detach should succeed");
+    let id = String::from_utf8_lossy(&started.stdout).trim().to_string();
+
+    let stop = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["stop", &id])
+        .output()
+        .expect("failed to execute rommy stop");
+    assert!(
+        stop.status.success(),
+        "stop should succeed, stderr: {}",
+        String::from_utf8_lossy(&stop.stderr)
+    );
+
+    let recs = parse_file(out_path).expect("stopped run should still be recorded");
+    assert_eq!(
+        recs[0].meta.get("status").map(String::as_str),
+        Some("killed")
+    );
+    assert_eq!(
+        recs[0].meta.get("signal").map(String::as_str),
+        Some("SIGTERM")
+    );
+
+    let status = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["status"])
+        .output()
+        .expect("failed to execute rommy status");
+    let listing = String::from_utf8_lossy(&status.stdout);
+    assert!(listing.contains("No active runs."), "unexpected: {listing}");
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::process::Command;
+
+fn record(out_path: &str, append: bool, script: &str) {
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let mut args = vec!["run", "--no-stream", "--out", out_path];
+    if append {
+        args.push("--append");
+    }
+    args.extend(["--", "bash", "-c", script]);
+    Command::new(bin)
+        .args(&args)
+        .output()
+        .expect("failed to execute rommy run");
+}
+
+#[test]
+fn diff_ignores_normalised_noise_and_reports_real_changes() {
+    let out_path = "target/tmp/diff_records.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let noisy = "echo \"at $(date -Iseconds)\"; echo \"took 0.${RANDOM}s\"; echo result=1";
+    record(out_path, false, noisy);
+    record(out_path, true, noisy);
+    record(out_path, true, "echo result=2; exit 4");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let same = Command::new(bin)
+        .args(["diff", "--color", "never"])
+        .arg(format!("{out_path}#1"))
+        .arg(format!("{out_path}#2"))
+        .output()
+        .expect("failed to execute rommy diff");
+    let text = String::from_utf8_lossy(&same.stdout);
+    assert_eq!(same.status.code(), Some(0), "unexpected diff: {text}");
+    assert!(text.contains("Records are identical."), "{text}");
+
+    let changed = Command::new(bin)
+        .args(["diff", "--color", "never", "--format", "json"])
+        .arg(format!("{out_path}#1"))
+        .arg(out_path)
+        .output()
+        .expect("failed to execute rommy diff");
+    assert_eq!(changed.status.code(), Some(1));
+    let value: serde_json::Value =
+        serde_json::from_slice(&changed.stdout).expect("diff --format json should emit JSON");
+    assert_eq!(value["identical"], false);
+    assert_eq!(value["b"], format!("{out_path}#3"));
+    let exit_code = value["meta"]
+        .as_array()
+        .expect("meta should be an array")
+        .iter()
+        .find(|m| m["key"] == "exit_code")
+        .expect("exit_code should differ");
+    assert_eq!(exit_code["a"], "0");
+    assert_eq!(exit_code["b"], "4");
+    let stdout = value["stdout"].as_str().expect("stdout diff expected");
+    assert!(stdout.contains("-result=1") && stdout.contains("+result=2"));
+}
+
+#[test]
+fn diff_without_normalisers_sees_timestamps() {
+    let out_path = "target/tmp/diff_raw.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    record(out_path, false, "date +%s%N");
+    record(out_path, true, "date +%s%N");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args(["diff", "--no-normalize", "--format", "side-by-side"])
+        .arg(format!("{out_path}#1"))
+        .arg(format!("{out_path}#2"))
+        .output()
+        .expect("failed to execute rommy diff");
+    assert_eq!(output.status.code(), Some(1));
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains("STDOUT") && text.contains(" | "), "{text}");
+}
+
+#[test]
+fn git_commit_is_read_from_packed_refs() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let repo = fs::canonicalize("target/tmp")
+        .expect("target/tmp should exist")
+        .join("diff_git_repo");
+    let _ = fs::remove_dir_all(&repo);
+    fs::create_dir_all(repo.join(".git")).expect("failed to create repo");
+    fs::create_dir_all(repo.join("sub")).expect("failed to create subdirectory");
+    let commit = "0123456789abcdef0123456789abcdef01234567";
+    fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").expect("failed to write HEAD");
+    fs::write(
+        repo.join(".git/packed-refs"),
+        format!("# pack-refs with: peeled fully-peeled sorted\n{commit} refs/heads/main\n"),
+    )
+    .expect("failed to write packed-refs");
+
+    let out = repo.join("git.rommy");
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .current_dir(repo.join("sub"))
+        .args(["run", "--no-stream", "--out"])
+        .arg(&out)
+        .args(["--", "true"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = rommy::parser::parse_file(&out).expect("failed to parse output");
+    assert_eq!(recs[0].meta["git_commit"], commit);
+}
@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn write_record(path: &str, start_ts: &str, status: &str, exit_code: i32) {
+    let path = Path::new(path);
+    fs::create_dir_all(path.parent().expect("record path has a parent"))
+        .expect("failed to create date directory");
+    let content = format!(
+        "<<<META>>>\nstart_ts: {start_ts}\nstatus: {status}\nexit_code: {exit_code}\n<<<END>>>\n\
+         <<<COMMAND>>>\n$ make\n<<<END>>>\n<<<STDOUT>>>\nout\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n"
+    );
+    fs::write(path, content).expect("failed to write record");
+}
+
+#[test]
+fn gc_removes_old_records_and_empty_date_dirs() {
+    let root = "target/tmp/gc_root";
+    let _ = fs::remove_dir_all(root);
+    let old_ok = format!("{root}/2020/01/02/100000.make.rommy");
+    let old_failed = format!("{root}/2020/01/03/100000.make.rommy");
+    let recent = format!("{root}/2099/01/01/100000.make.rommy");
+    write_record(&old_ok, "2020-01-02T10:00:00+00:00", "ok", 0);
+    write_record(&old_failed, "2020-01-03T10:00:00+00:00", "error", 2);
+    write_record(&recent, "2099-01-01T10:00:00+00:00", "ok", 0);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let args = [
+        "gc",
+        "--older-than",
+        "30d",
+        "--keep-failed",
+        "--color",
+        "never",
+    ];
+    let dry = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(args)
+        .arg("--dry-run")
+        .output()
+        .expect("failed to execute rommy gc --dry-run");
+    assert!(dry.status.success(), "dry run should succeed");
+    let report = String::from_utf8_lossy(&dry.stdout);
+    assert!(report.contains("Would remove 1 file(s)"), "{report}");
+    assert!(Path::new(&old_ok).exists(), "dry run must not delete");
+
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(args)
+        .output()
+        .expect("failed to execute rommy gc");
+    assert!(output.status.success(), "gc should succeed");
+    assert!(!Path::new(&old_ok).exists(), "old record should be removed");
+    assert!(
+        !Path::new(&format!("{root}/2020/01/02")).exists(),
+        "empty date directory should be removed"
+    );
+    assert!(Path::new(&old_failed).exists(), "--keep-failed keeps it");
+    assert!(Path::new(&recent).exists(), "recent record is kept");
+}
+
+#[test]
+fn configured_auto_gc_runs_after_each_run() {
+    let root = "target/tmp/gc_auto_root";
+    let _ = fs::remove_dir_all(root);
+    let old = format!("{root}/2020/01/02/100000.make.rommy");
+    write_record(&old, "2020-01-02T10:00:00+00:00", "ok", 0);
+    fs::write(
+        format!("{root}/rommy.conf"),
+        "# retention\ngc.auto: true\ngc.older_than: 7d\n",
+    )
+    .expect("failed to write rommy.conf");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["run", "--no-stream", "--color", "never", "--", "echo", "hi"])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(output.status.success(), "rommy run should succeed");
+    let notes = String::from_utf8_lossy(&output.stderr);
+    assert!(notes.contains("gc: removed 1 old file(s)"), "{notes}");
+    assert!(
+        !Path::new(&old).exists(),
+        "auto gc should remove the old record"
+    );
+
+    // Throttled by gc.interval: the next run within the hour leaves the store alone
+    write_record(&old, "2020-01-02T10:00:00+00:00", "ok", 0);
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["run", "--no-stream", "--color", "never", "--", "echo", "hi"])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(output.status.success(), "rommy run should succeed");
+    assert!(
+        Path::new(&old).exists(),
+        "auto gc should wait for gc.interval"
+    );
+}
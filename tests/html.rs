@@ -0,0 +1,89 @@
+use std::fs;
+use std::process::Command;
+
+const RECORDS: &str = "<<<META>>>
+label: build
+duration_ms: 1500
+status: ok
+exit_code: 0
+<<<END>>>
+<<<COMMAND>>>
+$ cargo build <release>
+<<<END>>>
+<<<STDOUT>>>
+\x1b[1;32mFinished\x1b[0m release
+<<<END>>>
+<<<STDERR>>>
+<<<END>>>
+<<<META>>>
+duration_ms: 250
+status: error
+exit_code: 101
+<<<END>>>
+<<<COMMAND>>>
+$ cargo test
+<<<END>>>
+<<<STDOUT>>>
+<<<END>>>
+<<<STDERR>>>
+\x1b[38;5;196mpanicked\x1b[0m
+<<<END>>>
+";
+
+#[test]
+fn export_html_writes_self_contained_report() {
+    let path = "target/tmp/export_html.rommy";
+    let out = "target/tmp/export_html.html";
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    fs::write(path, RECORDS).expect("failed to write fixture");
+    let _ = fs::remove_file(out);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args(["export", "html", path, "-o", out])
+        .output()
+        .expect("failed to execute rommy export html");
+    assert!(
+        output.status.success(),
+        "export should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let html = fs::read_to_string(out).expect("-o should write the report");
+    assert!(
+        html.starts_with("<!DOCTYPE html>"),
+        "unexpected html: {html}"
+    );
+    assert!(
+        html.contains("2 record(s), 1 failed"),
+        "unexpected html: {html}"
+    );
+    assert!(
+        html.contains("<a href=\"#rec-1\">target/tmp/export_html.rommy#1</a>"),
+        "unexpected html: {html}"
+    );
+    assert!(
+        html.contains("<td class=\"status-fail\">error</td>"),
+        "unexpected html: {html}"
+    );
+    assert!(
+        html.contains("$ cargo build &lt;release&gt;"),
+        "command should be escaped: {html}"
+    );
+    assert!(
+        html.contains("<span style=\"color:#0dbc79;font-weight:bold;\">Finished</span> release"),
+        "colors should become CSS: {html}"
+    );
+    assert!(
+        html.contains("<span style=\"color:#ff0000;\">panicked</span>"),
+        "256-color codes should become CSS: {html}"
+    );
+    assert!(
+        !html.contains('\x1b'),
+        "escape codes must not reach the HTML"
+    );
+    assert!(
+        !html.contains("src=\"http") && !html.contains("href=\"http"),
+        "report must not load external resources"
+    );
+}
@@ -0,0 +1,169 @@
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+
+/// Import `source` and return the imported record as `show --format json` sees it.
+fn import(from: &str, source: &str, out: &str, extra: &[&str]) -> Value {
+    let _ = fs::remove_file(out);
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let mut args = vec!["import", "--from", from, source, "--out", out];
+    args.extend_from_slice(extra);
+    let output = Command::new(bin)
+        .args(&args)
+        .output()
+        .expect("failed to execute rommy import");
+    assert!(
+        output.status.success(),
+        "import should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let validate = Command::new(bin)
+        .args(["validate", out])
+        .status()
+        .expect("failed to execute rommy validate");
+    assert!(validate.success(), "imported file should validate");
+
+    let show = Command::new(bin)
+        .args(["show", "--format", "json", out])
+        .output()
+        .expect("failed to execute rommy show");
+    let value: Value = serde_json::from_slice(&show.stdout).expect("show should emit JSON");
+    value["records"][0].clone()
+}
+
+#[test]
+fn import_script_typescript_with_timing() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let source = "target/tmp/import_typescript";
+    let timing = "target/tmp/import_typescript.timing";
+    fs::write(
+        source,
+        "Script started on 2025-10-26 16:59:00+01:00 [COMMAND=\"make test\" TERM=\"xterm-256color\" TTY=\"/dev/pts/1\" COLUMNS=\"120\" LINES=\"40\"]\n\
+         building\r\n\x1b[31mfailed\x1b[0m\r\n\
+         \nScript done on 2025-10-26 16:59:03+01:00 [COMMAND_EXIT_CODE=\"2\"]\n",
+    )
+    .expect("failed to write typescript");
+    fs::write(timing, "0.500000 10\n1.250000 20\n").expect("failed to write timing");
+
+    let record = import(
+        "script",
+        source,
+        "target/tmp/import_typescript.rommy",
+        &["--timing", timing, "--ansi", "strip"],
+    );
+    let meta = &record["meta"];
+    assert_eq!(meta["imported_from"], "script");
+    assert_eq!(meta["command_line"], "make test");
+    assert_eq!(meta["start_ts"], "2025-10-26T15:59:00+00:00");
+    assert_eq!(meta["end_ts"], "2025-10-26T15:59:03+00:00");
+    assert_eq!(meta["duration_ms"], "1750");
+    assert_eq!(meta["exit_code"], "2");
+    assert_eq!(meta["status"], "error");
+    assert_eq!(meta["terminal_cols"], "120");
+    assert_eq!(meta["unknown_fields"], "cwd, user, host");
+    assert_eq!(record["command"], "$ make test");
+    assert_eq!(record["stdout"], "building\nfailed");
+}
+
+#[test]
+fn import_asciicast_v2() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let source = "target/tmp/import_cast.cast";
+    fs::write(
+        source,
+        "{\"version\": 2, \"width\": 80, \"height\": 24, \"timestamp\": 1761494340, \"command\": \"cargo build\", \"title\": \"demo\", \"env\": {\"TERM\": \"xterm\", \"SHELL\": \"/bin/bash\"}}\n\
+         [0.1, \"o\", \"Compiling\\r\\n\"]\n\
+         [0.2, \"i\", \"q\"]\n\
+         [2.5, \"o\", \"Finished\\r\\n\"]\n",
+    )
+    .expect("failed to write cast");
+
+    let record = import("asciicast", source, "target/tmp/import_cast.rommy", &[]);
+    let meta = &record["meta"];
+    assert_eq!(meta["imported_from"], "asciicast");
+    assert_eq!(meta["command_line"], "cargo build");
+    assert_eq!(meta["title"], "demo");
+    assert_eq!(meta["term"], "xterm");
+    assert_eq!(meta["start_ts"], "2025-10-26T15:59:00+00:00");
+    assert_eq!(meta["duration_ms"], "2500");
+    assert_eq!(meta["unknown_fields"], "cwd, user, host, status, exit_code");
+    assert_eq!(record["stdout"], "Compiling\nFinished");
+}
+
+#[test]
+fn import_plain_log_marks_unknown_fields() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let source = "target/tmp/import_plain.log";
+    fs::write(source, "line one\nline two\n").expect("failed to write log");
+
+    let record = import(
+        "log",
+        source,
+        "target/tmp/import_plain.rommy",
+        &["--label", "legacy"],
+    );
+    let meta = &record["meta"];
+    assert_eq!(meta["label"], "legacy");
+    assert!(meta["end_ts"].is_string(), "end_ts should come from mtime");
+    assert_eq!(
+        meta["unknown_fields"],
+        "cwd, user, host, command_line, start_ts, duration_ms, status, exit_code"
+    );
+    assert_eq!(record["command"], "");
+    assert_eq!(record["stdout"], "line one\nline two");
+}
+
+#[test]
+fn import_does_not_replace_existing_files() {
+    let dir = "target/tmp/import_existing";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let source = format!("{dir}/build.log");
+    let out = format!("{dir}/build.rommy");
+    fs::write(&source, "compiling\n").expect("failed to write log");
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let import = |extra: &[&str]| {
+        Command::new(bin)
+            .args(["import", "--from", "log", &source, "--out", &out])
+            .args(extra)
+            .env("ROMMY_ROOT", dir)
+            .output()
+            .expect("failed to execute rommy import")
+    };
+
+    assert!(import(&[]).status.success());
+    let first = fs::read_to_string(&out).unwrap();
+    let refused = import(&[]);
+    assert!(!refused.status.success(), "an existing --out must be kept");
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--append or --force"));
+    assert_eq!(fs::read_to_string(&out).unwrap(), first);
+
+    assert!(import(&["--append"]).status.success());
+    assert_eq!(rommy::parser::parse_file(&out).unwrap().len(), 2);
+    assert!(import(&["--force"]).status.success());
+    assert_eq!(rommy::parser::parse_file(&out).unwrap().len(), 1);
+
+    // Automatic names come from the recording's time: the same log twice gets two files
+    for _ in 0..2 {
+        let status = Command::new(bin)
+            .args(["import", "--from", "log", &source])
+            .env("ROMMY_ROOT", format!("{dir}/root"))
+            .status()
+            .expect("failed to execute rommy import");
+        assert!(status.success());
+    }
+    let mut files = Vec::new();
+    let mut stack = vec![std::path::PathBuf::from(format!("{dir}/root"))];
+    while let Some(path) = stack.pop() {
+        for entry in fs::read_dir(&path).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path.extension().is_some_and(|e| e == "rommy") {
+                files.push(path);
+            }
+        }
+    }
+    assert_eq!(files.len(), 2, "{files:?}");
+}
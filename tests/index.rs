@@ -0,0 +1,127 @@
+use std::fs;
+use std::process::Command;
+
+fn query(root: &str, sql: &str) -> serde_json::Value {
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["query", sql, "--format", "json"])
+        .output()
+        .expect("failed to execute rommy query");
+    assert!(
+        output.status.success(),
+        "query failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("query --format json should emit JSON")
+}
+
+#[test]
+fn run_updates_index_and_rebuild_recreates_it() {
+    let root = "target/tmp/index_root";
+    let _ = fs::remove_dir_all(root);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for script in ["echo first-run", "echo second-run >&2; exit 5"] {
+        let status = Command::new(bin)
+            .env("ROMMY_ROOT", root)
+            .args(["run", "--no-stream", "--", "bash", "-c", script])
+            .output()
+            .expect("failed to execute rommy run")
+            .status;
+        assert!(status.success(), "rommy run should succeed");
+    }
+
+    let rows = query(
+        root,
+        "SELECT exit_code, status, offset FROM records ORDER BY start_ts",
+    );
+    assert_eq!(
+        rows,
+        serde_json::json!([
+            { "exit_code": 0, "status": "ok", "offset": 0 },
+            { "exit_code": 5, "status": "error", "offset": 0 },
+        ])
+    );
+
+    let rows = query(
+        root,
+        "SELECT r.exit_code FROM output o JOIN records r ON r.id = o.rowid \
+         WHERE output MATCH 'stderr:\"second run\"'",
+    );
+    assert_eq!(rows, serde_json::json!([{ "exit_code": 5 }]));
+
+    fs::remove_file(format!("{root}/index.sqlite")).expect("index should exist");
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["index", "rebuild"])
+        .output()
+        .expect("failed to execute rommy index rebuild");
+    assert!(output.status.success(), "rebuild should succeed");
+    let rows = query(root, "SELECT count(*) AS n FROM records");
+    assert_eq!(rows, serde_json::json!([{ "n": 2 }]));
+}
+
+#[test]
+fn index_keeps_timing_and_meta_order() {
+    let root = "target/tmp/index_blocks_root";
+    let _ = fs::remove_dir_all(root);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args([
+            "run",
+            "--no-stream",
+            "--timing",
+            "--",
+            "bash",
+            "-c",
+            "echo hi",
+        ])
+        .output()
+        .expect("failed to execute rommy run")
+        .status;
+    assert!(status.success(), "rommy run should succeed");
+
+    let rows = query(
+        root,
+        "SELECT b.timing IS NOT NULL AS timed, json_extract(b.meta_entries, '$[0][0]') AS first \
+         FROM records r JOIN blocks b ON b.id = r.id",
+    );
+    assert_eq!(
+        rows,
+        serde_json::json!([{ "timed": 1, "first": "rommy_version" }])
+    );
+}
+
+#[test]
+fn rebuild_stores_record_offsets_from_the_parser() {
+    let root = "target/tmp/index_offsets_root";
+    let _ = fs::remove_dir_all(root);
+    fs::create_dir_all(root).expect("failed to create root");
+    let record = "<<<META>>>\r\nstatus: ok\r\n<<<END>>>\r\n<<<COMMAND>>>\r\n<<<END>>>\r\n\
+<<<STDOUT>>>\r\n\r\n<<<END>>>\r\n<<<STDERR>>>\r\n<<<END>>>\r\n";
+    fs::write(
+        format!("{root}/crlf.rommy"),
+        format!("noise\n{record}{record}"),
+    )
+    .expect("failed to write record");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .env("ROMMY_ROOT", root)
+        .args(["index", "rebuild"])
+        .output()
+        .expect("failed to execute rommy index rebuild")
+        .status;
+    assert!(status.success(), "rebuild should succeed");
+    let rows = query(root, "SELECT offset, length FROM records ORDER BY record");
+    let n = record.len();
+    assert_eq!(
+        rows,
+        serde_json::json!([
+            { "offset": 6, "length": n },
+            { "offset": 6 + n, "length": n },
+        ])
+    );
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::process::Command;
+
+const RECORDS: &str = "<<<META>>>
+label: build
+start_ts: 2026-01-02T03:04:05+00:00
+duration_ms: 1500
+status: ok
+exit_code: 0
+<<<END>>>
+<<<COMMAND>>>
+$ cargo build
+<<<END>>>
+<<<STDOUT>>>
+<done> & dusted
+<<<END>>>
+<<<STDERR>>>
+<<<END>>>
+<<<META>>>
+duration_ms: 250
+status: error
+exit_code: 101
+<<<END>>>
+<<<COMMAND>>>
+$ cargo test
+<<<END>>>
+<<<STDOUT>>>
+<<<END>>>
+<<<STDERR>>>
+line 1
+line 2
+\x1b[31mpanicked\x1b[0m
+<<<END>>>
+";
+
+#[test]
+fn export_junit_maps_records_to_testcases() {
+    let path = "target/tmp/export_junit.rommy";
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    fs::write(path, RECORDS).expect("failed to write fixture");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args(["export", "junit", path, "--tail-lines", "2"])
+        .output()
+        .expect("failed to execute rommy export junit");
+    assert!(
+        output.status.success(),
+        "export should succeed, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let xml = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        xml.contains("<testsuites name=\"rommy\" tests=\"2\" failures=\"1\""),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains(&format!("<testsuite name=\"{path}\" tests=\"2\"")),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains(
+            "<testcase name=\"build\" classname=\"target/tmp/export_junit.rommy\" time=\"1.500\">"
+        ),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains("<system-out>&lt;done&gt; &amp; dusted</system-out>"),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains("<testcase name=\"cargo test\""),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains(
+            "<failure message=\"exit code 101\" type=\"error\">line 2\npanicked</failure>"
+        ),
+        "unexpected xml: {xml}"
+    );
+    assert!(!xml.contains('\x1b'), "escape codes must not reach the XML");
+}
+
+#[test]
+fn export_junit_groups_by_label() {
+    let path = "target/tmp/export_junit_label.rommy";
+    let out = "target/tmp/export_junit_label.xml";
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    fs::write(path, RECORDS).expect("failed to write fixture");
+    let _ = fs::remove_file(out);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args(["export", "junit", path, "--group-by", "label", "--out", out])
+        .status()
+        .expect("failed to execute rommy export junit");
+    assert!(status.success(), "export should succeed");
+
+    let xml = fs::read_to_string(out).expect("--out should write the XML");
+    assert!(
+        xml.contains("<testsuite name=\"build\" tests=\"1\" failures=\"0\""),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains("timestamp=\"2026-01-02T03:04:05+00:00\""),
+        "unexpected xml: {xml}"
+    );
+    assert!(
+        xml.contains("<testsuite name=\"unlabeled\" tests=\"1\" failures=\"1\""),
+        "unexpected xml: {xml}"
+    );
+}
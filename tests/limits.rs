@@ -0,0 +1,121 @@
+#![cfg(unix)]
+
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn cpu_limit_is_recorded_as_status_reason() {
+    let out_path = "target/tmp/limits_cpu.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--limit-cpu",
+            "1s",
+            "--out",
+            out_path,
+            "--",
+            "bash",
+            "-c",
+            "while :; do :; done",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    let meta = &recs[0].meta;
+    assert_eq!(meta.get("limit_cpu_secs").map(String::as_str), Some("1"));
+    assert_eq!(meta.get("status").map(String::as_str), Some("limit_cpu"));
+}
+
+#[test]
+fn fsize_limit_is_recorded_as_status_reason() {
+    let out_path = "target/tmp/limits_fsize.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--limit-fsize",
+            "4K",
+            "--out",
+            out_path,
+            "--",
+            "dd",
+            "if=/dev/zero",
+            "of=target/tmp/limits_fsize.bin",
+            "bs=1k",
+            "count=64",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    let meta = &recs[0].meta;
+    assert_eq!(
+        meta.get("limit_fsize_bytes").map(String::as_str),
+        Some("4096")
+    );
+    assert_eq!(meta.get("status").map(String::as_str), Some("limit_fsize"));
+}
+
+#[test]
+fn invalid_limit_value_is_rejected() {
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args(["run", "--limit-mem", "lots", "--", "true"])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(!output.status.success(), "invalid size should be rejected");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--limit-mem"), "unexpected: {stderr}");
+}
+
+#[test]
+fn overflowing_duration_is_rejected() {
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args(["run", "--limit-cpu", "999999999999999999d", "--", "true"])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(!output.status.success(), "overflow should be rejected");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("too large"), "unexpected: {stderr}");
+}
+
+#[test]
+fn memory_message_before_an_ordinary_failure_is_not_a_limit() {
+    let out = "target/tmp/limits_mem_message.rommy";
+    let _ = fs::remove_file(out);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--limit-mem",
+            "1G",
+            "--out",
+            out,
+            "--",
+            "bash",
+            "-c",
+            "echo 'retrying: Cannot allocate memory' >&2; echo 'config missing' >&2; exit 3",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+    let recs = parse_file(out).expect("failed to parse output");
+    assert_eq!(recs[0].meta["status"], "error");
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn ls_lists_default_store_newest_first_with_filters() {
+    let root = "target/tmp/ls_root";
+    let _ = fs::remove_dir_all(root);
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for args in [
+        vec![
+            "run",
+            "--no-stream",
+            "--label",
+            "first",
+            "--",
+            "echo",
+            "one",
+        ],
+        vec!["run", "--no-stream", "--", "bash", "-c", "exit 3"],
+    ] {
+        Command::new(bin)
+            .env("ROMMY_ROOT", root)
+            .args(&args)
+            .output()
+            .expect("failed to execute rommy run");
+    }
+
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["ls", "--format", "json"])
+        .output()
+        .expect("failed to execute rommy ls");
+    assert!(output.status.success(), "rommy ls should succeed");
+    let value: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("ls --format json should emit JSON");
+    let records = value["records"].as_array().expect("records array");
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0]["exit_code"], 3,
+        "newest record should come first"
+    );
+    assert_eq!(records[1]["label"], "first");
+
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args([
+            "ls", "--status", "error", "--since", "1h", "--color", "never",
+        ])
+        .output()
+        .expect("failed to execute rommy ls");
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(text.lines().count(), 1, "unexpected listing: {text}");
+    assert!(text.contains("exit   3"), "unexpected listing: {text}");
+
+    let output = Command::new(bin)
+        .env("ROMMY_ROOT", root)
+        .args(["ls", "--label", "first", "--grep-command", "^\\$ 'echo'"])
+        .output()
+        .expect("failed to execute rommy ls");
+    let text = String::from_utf8_lossy(&output.stdout);
+    assert!(text.contains("[first]"), "unexpected listing: {text}");
+    assert_eq!(text.lines().count(), 1, "unexpected listing: {text}");
+}
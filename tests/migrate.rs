@@ -0,0 +1,147 @@
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+const V1_RECORD: &str = "<<<META>>>\nrommy_version: 0.0.9\ntimestamp: 2025-10-22T15:30:45Z\nexit_code: 3\nduration_ms: 1500\n<<<END>>>\n\
+<<<COMMAND>>>\n$ cargo test\n<<<END>>>\n<<<STDOUT>>>\nrunning 3 tests\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+
+fn migrate(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .arg("migrate")
+        .args(args)
+        .output()
+        .expect("failed to execute rommy migrate")
+}
+
+#[test]
+fn migrate_upgrades_old_layout_in_place() {
+    let dir = "target/tmp/migrate_v1";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/old.rommy");
+    fs::write(&path, V1_RECORD.repeat(2)).expect("failed to write old record");
+
+    let dry = migrate(&["--dry-run", dir]);
+    assert!(dry.status.success(), "dry run should succeed");
+    let diff = String::from_utf8_lossy(&dry.stdout);
+    assert!(diff.contains("-timestamp: 2025-10-22T15:30:45Z"), "{diff}");
+    assert!(diff.contains("+start_ts: 2025-10-22T15:30:45Z"), "{diff}");
+    assert!(diff.contains("+format_version: 2"), "{diff}");
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        V1_RECORD.repeat(2),
+        "dry run must not touch the file"
+    );
+
+    let run = migrate(&[&path]);
+    assert!(run.status.success(), "migrate should succeed");
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(!text.contains("timestamp:"), "{text}");
+    let records = parse_file(&path).expect("migrated file should parse");
+    assert_eq!(records.len(), 2);
+    let meta = &records[0].meta;
+    assert_eq!(meta["format_version"], "2");
+    assert_eq!(meta["start_ts"], "2025-10-22T15:30:45Z");
+    assert_eq!(meta["end_ts"], "2025-10-22T15:30:46.500+00:00");
+    assert_eq!(meta["status"], "error");
+    assert_eq!(meta["command_line"], "cargo test");
+    assert_eq!(meta["unknown_fields"], "cwd, user, host");
+
+    let strict = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["validate", "--strict", &path])
+        .status()
+        .expect("failed to execute rommy validate");
+    assert!(
+        strict.success(),
+        "migrated file should pass strict validation"
+    );
+
+    // Already current: nothing to do
+    let again = migrate(&["--dry-run", &path]);
+    assert!(again.status.success());
+    assert!(again.stdout.is_empty(), "no diff expected");
+}
+
+#[test]
+fn newer_format_version_is_rejected() {
+    let dir = "target/tmp/migrate_future";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/future.rommy");
+    let future = V1_RECORD.replace("timestamp:", "format_version: 99\ntimestamp:");
+    fs::write(&path, &future).expect("failed to write record");
+
+    let run = migrate(&[&path]);
+    assert!(
+        !run.status.success(),
+        "unknown versions must not be rewritten"
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), future);
+
+    let validate = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["validate", "--format", "ndjson", &path])
+        .output()
+        .expect("failed to execute rommy validate");
+    let stdout = String::from_utf8_lossy(&validate.stdout);
+    assert!(
+        stdout.contains("\"error_kind\":\"unsupported_version\""),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn migrate_rewrites_only_meta() {
+    let dir = "target/tmp/migrate_verbatim";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/old.rommy");
+    let old = "# captured on the old laptop\n\
+<<<META>>>\ntimestamp: 2025-10-22T15:30:45Z\nnote without colon\nexit_code: 0\nduration_ms: 20\n<<<END>>>\n\
+<<<COMMAND>>>\n$ ls\n<<<END>>>\nbetween blocks\n<<<STDOUT>>>\n\n\nfirst line after two blank ones\n<<<END>>>\n\
+<<<STDERR>>>\n<<<END>>>\n";
+    fs::write(&path, old).expect("failed to write old record");
+
+    let dry = migrate(&["--dry-run", &path]);
+    assert!(dry.status.success(), "dry run should succeed");
+    let entries: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(
+        entries.len(),
+        1,
+        "a dry run must not create files: {entries:?}"
+    );
+
+    assert!(migrate(&[&path]).status.success(), "migrate should succeed");
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(
+        text.starts_with("# captured on the old laptop\n<<<META>>>\n"),
+        "{text}"
+    );
+    assert!(text.contains("\nnote without colon\n"), "{text}");
+    let rest = &old[old.find("<<<COMMAND>>>").unwrap()..];
+    assert!(
+        text.ends_with(rest),
+        "blocks must be copied verbatim: {text}"
+    );
+
+    let records = parse_file(&path).expect("migrated file should parse");
+    // `validate --strict` does not ask for keys the old layout did not know
+    assert_eq!(
+        records[0].meta["unknown_fields"],
+        "rommy_version, cwd, user, host"
+    );
+}
+
+#[test]
+fn parsed_v1_record_reports_its_source_version() {
+    let records = rommy::parser::parse_str(V1_RECORD).expect("v1 record should parse");
+    assert_eq!(records[0].format_version(), 1);
+    // META is already upgraded on read
+    assert_eq!(records[0].meta["start_ts"], "2025-10-22T15:30:45Z");
+
+    let current = rommy::parser::parse_str(&V1_RECORD.replace("timestamp:", "start_ts:"))
+        .expect("current record should parse");
+    assert_eq!(current[0].format_version(), rommy::parser::FORMAT_VERSION);
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn no_stream_failure_prints_output_tail_before_wrote() {
+    let out_path = "target/tmp/no_stream_tail.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--tail-lines",
+            "2",
+            "--out",
+            out_path,
+            "--",
+            "bash",
+            "-c",
+            "for i in 1 2 3; do echo out-$i; echo err-$i >&2; done; exit 3",
+        ])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(output.status.success(), "rommy itself should succeed");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("last 2 line(s) of stderr"),
+        "unexpected stderr: {stderr}"
+    );
+    assert!(!stderr.contains("err-1"), "tail too long: {stderr}");
+    let err3 = stderr.find("err-3").expect("stderr tail missing");
+    let out3 = stderr.find("out-3").expect("stdout tail missing");
+    let wrote = stderr.find("Wrote").expect("Wrote note missing");
+    assert!(
+        err3 < wrote && out3 < wrote,
+        "tail must precede Wrote: {stderr}"
+    );
+    // Status line is only drawn on a terminal
+    assert!(
+        !stderr.contains("[rommy]"),
+        "unexpected status line: {stderr}"
+    );
+}
+
+#[test]
+fn no_stream_success_prints_no_tail() {
+    let out_path = "target/tmp/no_stream_ok.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let output = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--out",
+            out_path,
+            "--",
+            "echo",
+            "fine",
+        ])
+        .output()
+        .expect("failed to execute rommy run");
+    assert!(output.status.success(), "rommy run should succeed");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("last "), "unexpected tail: {stderr}");
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("fine"));
+}
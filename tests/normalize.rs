@@ -0,0 +1,71 @@
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn progress_lines_keep_only_final_state() {
+    let out_path = "target/tmp/normalize_progress.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--out",
+            out_path,
+            "--",
+            "bash",
+            "-c",
+            "printf 'Downloading 10%%\\rDownloading 55%%\\rDone           \\r\\033[KDone\\n'; \
+             for i in $(seq 1 50); do echo 'Compiling same-crate v0.1.0'; done; echo tail",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    let r = &recs[0];
+    assert_eq!(
+        r.stdout,
+        "Done\nCompiling same-crate v0.1.0\n(repeated 50×)\ntail"
+    );
+    let removed: usize = r
+        .meta
+        .get("stdout_removed_bytes")
+        .and_then(|v| v.parse().ok())
+        .expect("stdout_removed_bytes should be recorded");
+    assert!(removed > 1000, "unexpected removed bytes: {removed}");
+    assert_eq!(
+        r.meta.get("stdout_collapsed_lines").map(String::as_str),
+        Some("49")
+    );
+}
+
+#[test]
+fn no_normalize_stores_raw_capture() {
+    let out_path = "target/tmp/normalize_raw.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--no-normalize",
+            "--out",
+            out_path,
+            "--",
+            "printf",
+            "1%%\\r2%%\\n",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let recs = parse_file(out_path).expect("failed to parse output");
+    assert_eq!(recs[0].stdout, "1%\r2%");
+    assert!(!recs[0].meta.contains_key("stdout_removed_bytes"));
+}
@@ -102,3 +102,141 @@ A
         "unexpected parser error: {msg}"
     );
 }
+
+#[test]
+fn record_reader_yields_records_one_at_a_time() {
+    use rommy::parser::RecordReader;
+
+    // CRLF line endings, followed by a record that is cut off
+    let sample = "<<<META>>>\r\nstatus: ok\r\n<<<END>>>\r\n<<<COMMAND>>>\r\n$ echo A\r\n<<<END>>>\r\n\
+                  <<<STDOUT>>>\r\nA\r\n<<<END>>>\r\n<<<STDERR>>>\r\n<<<END>>>\r\n\
+                  <<<META>>>\r\nstatus: error\r\n<<<END>>>\r\n<<<STDOUT>>>\r\npartial\r\n";
+
+    let mut reader = RecordReader::new(sample.as_bytes());
+    let first = reader
+        .next()
+        .expect("first record")
+        .expect("first record is complete");
+    assert_eq!(first.meta.get("status").map(String::as_str), Some("ok"));
+    assert_eq!(first.stdout, "A");
+
+    let err = reader
+        .next()
+        .expect("second item")
+        .expect_err("second record is cut off");
+    assert!(err.to_string().contains("unexpected EOF"), "{err}");
+    assert!(reader.next().is_none(), "reader stops after an error");
+}
+
+#[test]
+fn parse_errors_carry_their_position() {
+    use rommy::parser::{ParseError, RecordReader};
+
+    let sample = "noise before the first record\n<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n$ echo A\n";
+    let err = RecordReader::new(sample.as_bytes())
+        .next()
+        .expect("one item")
+        .expect_err("COMMAND is never closed");
+    match &err {
+        ParseError::UnclosedBlock { block, at } => {
+            assert_eq!(*block, "COMMAND");
+            assert_eq!((at.line, at.record, at.offset), (5, 1, 62));
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+    assert_eq!(err.kind(), "unclosed_block");
+    assert_eq!(
+        err.to_string(),
+        "line 5: unexpected EOF: COMMAND block not closed with <<<END>>>"
+    );
+
+    // Lines without a colon are only rejected on request
+    let meta = "<<<META>>>\nstatus ok\n<<<END>>>\n";
+    let err = RecordReader::new(meta.as_bytes())
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(err, ParseError::MissingBlocks { .. }), "{err:?}");
+    let err = RecordReader::new(meta.as_bytes())
+        .strict_meta(true)
+        .next()
+        .unwrap()
+        .expect_err("strict META");
+    assert!(
+        matches!(err, ParseError::InvalidMetaLine { ref line, at } if line == "status ok" && at.line == 2),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn meta_keeps_file_order_and_duplicates() {
+    use rommy::writer::write_record;
+
+    let sample = "<<<META>>>\nzeta: 1\nstatus: ok\nalpha: 2\nstatus: error\n<<<END>>>\n\
+<<<COMMAND>>>\n<<<END>>>\n<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+    let recs = parse_str(sample).expect("parse failed");
+    let meta = &recs[0].meta;
+    assert_eq!(meta["status"], "error", "the last value wins");
+    assert_eq!(meta.get_all("status").collect::<Vec<_>>(), ["ok", "error"]);
+    assert_eq!(meta.keys().collect::<Vec<_>>(), ["zeta", "status", "alpha"]);
+    assert_eq!(meta.duplicates(), ["status"]);
+
+    let mut written = Vec::new();
+    write_record(&mut written, &recs[0]).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(
+        written.starts_with("<<<META>>>\nzeta: 1\nstatus: ok\nalpha: 2\nstatus: error\n"),
+        "{written}"
+    );
+}
+
+#[test]
+fn typed_meta_accessors() {
+    use rommy::meta::Status;
+    use std::time::Duration;
+
+    let sample = "<<<META>>>\nlabel: nightly\nstart_ts: 2025-10-22T15:30:45+02:00\nduration_ms: 1500\n\
+status: limit_mem\nexit_code: 137\n<<<END>>>\n\
+<<<COMMAND>>>\n<<<END>>>\n<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+    let r = &parse_str(sample).expect("parse failed")[0];
+    assert_eq!(r.exit_code(), Ok(Some(137)));
+    assert_eq!(
+        r.start().unwrap().unwrap().to_rfc3339(),
+        "2025-10-22T13:30:45+00:00"
+    );
+    assert_eq!(r.end(), Ok(None));
+    assert_eq!(r.duration(), Ok(Some(Duration::from_millis(1500))));
+    assert_eq!(r.status(), Ok(Some(Status::LimitMem)));
+    assert_eq!(r.label(), Some("nightly"));
+
+    let bad = sample
+        .replace("exit_code: 137", "exit_code: lots")
+        .replace("duration_ms: 1500", "duration_ms: -3")
+        .replace("status: limit_mem", "status: exploded");
+    let r = &parse_str(&bad).expect("parse failed")[0];
+    let err = r.exit_code().expect_err("malformed exit_code");
+    assert_eq!(
+        (err.key.as_str(), err.value.as_str()),
+        ("exit_code", "lots")
+    );
+    assert_eq!(err.to_string(), "`exit_code` is not an integer (\"lots\")");
+    assert_eq!(r.duration().unwrap_err().key, "duration_ms");
+    assert_eq!(r.status().unwrap_err().value, "exploded");
+}
+
+#[test]
+fn record_reader_reports_record_start_offsets() {
+    use rommy::parser::RecordReader;
+
+    let record = "<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n<<<END>>>\n\
+<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+    let text = format!("noise\n{record}{record}");
+    let mut reader = RecordReader::new(text.as_bytes());
+    let mut starts = Vec::new();
+    while let Some(parsed) = reader.next() {
+        parsed.expect("valid record");
+        let at = reader.record_start().expect("start of the record");
+        starts.push((at.line, at.offset));
+    }
+    assert_eq!(starts, [(2, 6), (11, 6 + record.len() as u64)]);
+}
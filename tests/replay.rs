@@ -0,0 +1,135 @@
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+use std::time::Instant;
+
+/// Record a short command with `run --timing` into `out`.
+fn record_with_timing(out: &str) {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let _ = fs::remove_file(out);
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args([
+            "run",
+            "--timing",
+            "--no-stream",
+            "--out",
+            out,
+            "--",
+            "bash",
+            "-c",
+            "echo first; sleep 0.3; echo oops >&2; sleep 0.3; echo second",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "run --timing should succeed");
+}
+
+#[test]
+fn run_timing_writes_timing_block() {
+    let out = "target/tmp/replay_timing.rommy";
+    record_with_timing(out);
+
+    let content = fs::read_to_string(out).expect("failed to read record");
+    let block = content
+        .split("<<<TIMING>>>\n")
+        .nth(1)
+        .and_then(|rest| rest.split("<<<END>>>").next())
+        .expect("record should contain a TIMING block");
+    let chunks: Vec<(u64, &str, usize)> = block
+        .lines()
+        .map(|line| {
+            let fields: Vec<&str> = line.split(' ').collect();
+            (
+                fields[0].parse().unwrap(),
+                fields[1],
+                fields[2].parse().unwrap(),
+            )
+        })
+        .collect();
+    assert!(chunks.windows(2).all(|w| w[0].0 <= w[1].0), "{block}");
+    let stdout_bytes: usize = chunks.iter().filter(|c| c.1 == "o").map(|c| c.2).sum();
+    assert_eq!(stdout_bytes, "first\nsecond\n".len(), "{block}");
+    let last = chunks.iter().rfind(|c| c.1 == "o").unwrap();
+    assert!(last.0 >= 500, "second line should arrive late: {block}");
+
+    let validate = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["validate", out])
+        .status()
+        .expect("failed to execute rommy validate");
+    assert!(validate.success(), "record with TIMING should validate");
+
+    let show = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["show", "--format", "json", out])
+        .output()
+        .expect("failed to execute rommy show");
+    let value: Value = serde_json::from_slice(&show.stdout).expect("show should emit JSON");
+    assert_eq!(value["records"][0]["timing"], block.trim_end_matches('\n'));
+}
+
+#[test]
+fn replay_plays_output_at_requested_speed() {
+    let out = "target/tmp/replay_speed.rommy";
+    record_with_timing(out);
+
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["replay", out, "--record", "1", "--speed", "100x"])
+        .output()
+        .expect("failed to execute rommy replay");
+    assert!(output.status.success(), "replay should succeed");
+    assert!(
+        started.elapsed().as_secs() < 5,
+        "100x replay should be quick"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("first\nsecond"), "stdout: {stdout:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("oops"));
+}
+
+#[test]
+fn export_asciicast_writes_v2_file() {
+    let out = "target/tmp/replay_export.rommy";
+    let cast = "target/tmp/replay_export.cast";
+    record_with_timing(out);
+    let _ = fs::remove_file(cast);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["export", "asciicast", out, "-o", cast, "--cols", "100"])
+        .status()
+        .expect("failed to execute rommy export asciicast");
+    assert!(status.success(), "export asciicast should succeed");
+
+    let text = fs::read_to_string(cast).expect("failed to read cast");
+    let mut lines = text.lines();
+    let header: Value = serde_json::from_str(lines.next().unwrap()).expect("header is JSON");
+    assert_eq!(header["version"], 2);
+    assert_eq!(header["width"], 100);
+    assert_eq!(header["height"], 24);
+
+    let events: Vec<Value> = lines
+        .map(|line| serde_json::from_str(line).expect("event is JSON"))
+        .collect();
+    assert!(events.iter().all(|e| e[1] == "o"));
+    let output: String = events.iter().map(|e| e[2].as_str().unwrap()).collect();
+    assert!(output.contains("first\r\n"), "output: {output:?}");
+    assert!(output.contains("\x1b[33moops"), "stderr should be yellow");
+}
+
+#[test]
+fn replay_without_timing_fails() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let out = "target/tmp/replay_plain.rommy";
+    let _ = fs::remove_file(out);
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["run", "--no-stream", "--out", out, "--", "echo", "hi"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["replay", out])
+        .output()
+        .expect("failed to execute rommy replay");
+    assert!(!output.status.success(), "replay needs a TIMING block");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--timing"));
+}
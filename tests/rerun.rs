@@ -0,0 +1,172 @@
+use rommy::parser::parse_file;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn rerun_restores_env_and_links_to_original() {
+    let first = "target/tmp/rerun_first.rommy";
+    let second = "target/tmp/rerun_second.rommy";
+    let _ = fs::remove_file(first);
+    let _ = fs::remove_file(second);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--env",
+            "ROMMY_RERUN_VALUE=hello",
+            "--out",
+            first,
+            "--",
+            "bash",
+            "-c",
+            "echo value=$ROMMY_RERUN_VALUE",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+    let recorded = parse_file(first).expect("failed to parse first output");
+    assert_eq!(recorded[0].meta["env_keys"], "ROMMY_RERUN_VALUE");
+    assert!(
+        !recorded[0].meta.contains_key("env.ROMMY_RERUN_VALUE"),
+        "values are only recorded with --record-env"
+    );
+
+    // The value comes from the environment rerun is started in
+    let output = Command::new(bin)
+        .args(["rerun", first, "--no-stream", "--out", second])
+        .env("ROMMY_RERUN_VALUE", "hello")
+        .output()
+        .expect("failed to execute rommy rerun");
+    assert!(output.status.success(), "rommy rerun should succeed");
+    let summary = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        summary.contains("exit code: 0 -> 0 (unchanged)"),
+        "unexpected summary: {summary}"
+    );
+    assert!(summary.contains("stdout:    identical"), "{summary}");
+
+    let recs = parse_file(second).expect("failed to parse rerun output");
+    let meta = &recs[0].meta;
+    assert_eq!(recs[0].stdout, "value=hello");
+    assert_eq!(meta["env_keys"], "ROMMY_RERUN_VALUE");
+    assert!(!meta.contains_key("env.ROMMY_RERUN_VALUE"));
+    let rerun_of = meta.get("rerun_of").expect("rerun_of should be recorded");
+    assert!(rerun_of.ends_with("rerun_first.rommy#1"), "got {rerun_of}");
+}
+
+#[test]
+fn rerun_replays_recorded_script_content() {
+    let script = "target/tmp/rerun_script.sh";
+    let first = "target/tmp/rerun_script_first.rommy";
+    let second = "target/tmp/rerun_script_second.rommy";
+    let _ = fs::remove_file(first);
+    let _ = fs::remove_file(second);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    fs::write(script, "echo original\n").expect("failed to write script");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args(["run", "--no-stream", "--script", script, "--out", first])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    // Editing the script afterwards must not change what rerun executes
+    fs::write(script, "echo edited\n").expect("failed to rewrite script");
+
+    let status = Command::new(bin)
+        .args([
+            "rerun",
+            first,
+            "--record",
+            "1",
+            "--no-stream",
+            "--out",
+            second,
+        ])
+        .status()
+        .expect("failed to execute rommy rerun");
+    assert!(status.success(), "rommy rerun should succeed");
+
+    let original = parse_file(first).expect("failed to parse first output");
+    let rerun = parse_file(second).expect("failed to parse rerun output");
+    assert_eq!(rerun[0].stdout, "original");
+    assert_eq!(rerun[0].command, original[0].command);
+    assert_eq!(
+        rerun[0].meta["script_path"], original[0].meta["script_path"],
+        "the scratch copy must not show up in META"
+    );
+}
+
+#[test]
+fn rerun_uses_values_recorded_with_record_env() {
+    let first = "target/tmp/rerun_record_env_first.rommy";
+    let second = "target/tmp/rerun_record_env_second.rommy";
+    let _ = fs::remove_file(first);
+    let _ = fs::remove_file(second);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--record-env",
+            "--env",
+            "ROMMY_RERUN_SAVED=kept",
+            "--out",
+            first,
+            "--",
+            "bash",
+            "-c",
+            "echo value=$ROMMY_RERUN_SAVED",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let status = Command::new(bin)
+        .args(["rerun", first, "--no-stream", "--out", second])
+        .env("ROMMY_RERUN_SAVED", "ignored")
+        .status()
+        .expect("failed to execute rommy rerun");
+    assert!(status.success(), "rommy rerun should succeed");
+
+    let recs = parse_file(second).expect("failed to parse rerun output");
+    assert_eq!(recs[0].stdout, "value=kept");
+    assert_eq!(recs[0].meta["env.ROMMY_RERUN_SAVED"], "kept");
+}
+
+#[test]
+fn rerun_runs_unchanged_script_from_its_own_path() {
+    let dir = "target/tmp/rerun_script_dir";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create script dir");
+    let script = format!("{dir}/main.sh");
+    fs::write(format!("{dir}/helper.sh"), "greeting=from-helper\n").expect("write helper");
+    fs::write(
+        &script,
+        "source \"$(dirname \"$0\")/helper.sh\"\necho $greeting\n",
+    )
+    .expect("write script");
+    let first = format!("{dir}/first.rommy");
+    let second = format!("{dir}/second.rommy");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let status = Command::new(bin)
+        .args(["run", "--no-stream", "--script", &script, "--out", &first])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+
+    let status = Command::new(bin)
+        .args(["rerun", &first, "--no-stream", "--out", &second])
+        .status()
+        .expect("failed to execute rommy rerun");
+    assert!(status.success(), "rommy rerun should succeed");
+    let recs = parse_file(&second).expect("failed to parse rerun output");
+    assert_eq!(recs[0].stdout, "from-helper");
+}
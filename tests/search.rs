@@ -0,0 +1,100 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn search_reports_record_block_and_line() {
+    let out_path = "target/tmp/search_records.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for (append, script) in [
+        (false, "echo all good"),
+        (
+            true,
+            "printf 'one\\ntwo\\nthread main panicked at src/lib.rs\\nfour\\n'",
+        ),
+    ] {
+        let mut cmd = Command::new(bin);
+        cmd.args(["run", "--no-stream", "--out", out_path]);
+        if append {
+            cmd.arg("--append");
+        }
+        cmd.args(["--", "bash", "-c", script])
+            .output()
+            .expect("failed to execute rommy run");
+    }
+
+    let output = Command::new(bin)
+        .args([
+            "search",
+            "panicked at",
+            out_path,
+            "--in",
+            "stdout",
+            "-C",
+            "1",
+            "--color",
+            "never",
+        ])
+        .output()
+        .expect("failed to execute rommy search");
+    assert!(output.status.success(), "search should find the panic");
+    let text = String::from_utf8_lossy(&output.stdout);
+    let expected = format!(
+        "{out_path}#2:stdout-2-two\n\
+         {out_path}#2:stdout:3:thread main panicked at src/lib.rs\n\
+         {out_path}#2:stdout-4-four\n"
+    );
+    assert_eq!(text, expected);
+
+    // The pattern only occurs in COMMAND, which --in stdout excludes
+    let output = Command::new(bin)
+        .args(["search", "printf", out_path, "--in", "stdout"])
+        .output()
+        .expect("failed to execute rommy search");
+    assert_eq!(output.status.code(), Some(1), "no match should exit 1");
+
+    let output = Command::new(bin)
+        .args(["search", "GOOD", out_path, "-i", "--format", "json"])
+        .output()
+        .expect("failed to execute rommy search");
+    let value: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("search --format json should emit JSON");
+    let matches = value["matches"].as_array().expect("matches array");
+    assert_eq!(matches.len(), 2, "COMMAND and STDOUT of record 1: {value}");
+    assert!(matches.iter().all(|m| m["record"] == 1));
+}
+
+#[test]
+fn search_limit_stops_before_older_files() {
+    let dir = "target/tmp/search_limit";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    // Older and broken: only reported if search gets that far
+    fs::write(format!("{dir}/old.rommy"), "<<<META>>>\nstatus: ok\n")
+        .expect("failed to write broken file");
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let new = format!("{dir}/new.rommy");
+    Command::new(bin)
+        .args(["run", "--no-stream", "--out", &new, "--", "echo", "needle"])
+        .output()
+        .expect("failed to execute rommy run");
+
+    let search = |limit: &str| {
+        Command::new(bin)
+            .args(["search", "needle", dir, "--in", "stdout", "--limit", limit])
+            .output()
+            .expect("failed to execute rommy search")
+    };
+    let output = search("1");
+    assert!(output.status.success(), "search should find the match");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("unreadable"), "read too far: {stderr}");
+
+    let output = search("2");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Skipped 1 unreadable"), "{stderr}");
+}
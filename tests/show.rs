@@ -130,3 +130,251 @@ fn show_record_out_of_range_fails() {
         "expected out-of-range message, got: {stderr}"
     );
 }
+
+#[test]
+fn show_record_stops_reading_after_the_selected_record() {
+    let out_path = "target/tmp/show_record_stream.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let run_status = Command::new(bin)
+        .args(["run", "--out", out_path, "--", "echo", "early-record"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(run_status.success(), "rommy run should succeed");
+    // A damaged tail is never reached when the first record is requested
+    let mut content = fs::read_to_string(out_path).expect("failed to read record");
+    content.push_str("<<<META>>>\nstatus: ok\n<<<STDOUT>>>\n");
+    fs::write(out_path, content).expect("failed to write record");
+
+    let show = Command::new(bin)
+        .args(["show", "--record", "1", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success(), "show --record 1 should succeed");
+    assert!(String::from_utf8_lossy(&show.stdout).contains("early-record"));
+
+    let show_all = Command::new(bin)
+        .args(["show", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(
+        !show_all.status.success(),
+        "show of all records should fail"
+    );
+}
+
+#[test]
+fn show_markdown_renders_fenced_blocks() {
+    let out_path = "target/tmp/show_markdown.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let run_status = Command::new(bin)
+        .args(["run", "--out", out_path, "--", "echo", "has ``` fence"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(run_status.success(), "rommy run should succeed");
+
+    let show = Command::new(bin)
+        .args(["show", "--format", "markdown", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success(), "show markdown should succeed");
+
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    assert!(
+        stdout.contains("## Record 1"),
+        "unexpected output: {stdout}"
+    );
+    assert!(
+        stdout.contains("- **exit code:** 0"),
+        "unexpected output: {stdout}"
+    );
+    assert!(stdout.contains("```bash\n"), "unexpected output: {stdout}");
+    assert!(
+        stdout.contains("````text\nhas ``` fence\n````\n"),
+        "unexpected output: {stdout}"
+    );
+}
+
+#[test]
+fn show_markdown_strips_kept_escape_codes() {
+    let out_path = "target/tmp/show_markdown_ansi.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let run_status = Command::new(bin)
+        .args([
+            "run",
+            "--no-stream",
+            "--out",
+            out_path,
+            "--",
+            "printf",
+            "\\033[31mred\\033[0m\\n",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(run_status.success(), "rommy run should succeed");
+    assert!(fs::read_to_string(out_path).unwrap().contains('\x1b'));
+
+    let show = Command::new(bin)
+        .args(["show", "--format", "markdown", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    assert!(stdout.contains("```text\nred\n```"), "{stdout}");
+    assert!(!stdout.contains('\x1b'), "{stdout}");
+}
+
+#[test]
+fn show_markdown_budget_keeps_head_and_tail() {
+    let out_path = "target/tmp/show_markdown_budget.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let run_status = Command::new(bin)
+        .args(["run", "--out", out_path, "--", "seq", "1", "5000"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(run_status.success(), "rommy run should succeed");
+
+    let show = Command::new(bin)
+        .args(["show", "--format", "markdown", "--budget", "200", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success(), "show markdown should succeed");
+
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    assert!(stdout.len() <= 200 * 4, "output exceeds budget: {stdout}");
+    assert!(stdout.contains("\n1\n2\n"), "head missing: {stdout}");
+    assert!(stdout.contains("\n5000\n"), "tail missing: {stdout}");
+    assert!(stdout.contains("omitted"), "marker missing: {stdout}");
+
+    let text = Command::new(bin)
+        .args(["show", "--budget", "200", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(
+        !text.status.success(),
+        "--budget without markdown should fail"
+    );
+}
+
+#[test]
+fn show_ndjson_writes_one_record_per_line() {
+    let out_path = "target/tmp/show_ndjson.rommy";
+    let _ = fs::remove_file(out_path);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    for word in ["first-ndjson", "second-ndjson"] {
+        let status = Command::new(bin)
+            .args(["run", "--append", "--out", out_path, "--", "echo", word])
+            .status()
+            .expect("failed to execute rommy run");
+        assert!(status.success(), "rommy run should succeed");
+    }
+
+    let show = Command::new(bin)
+        .args(["show", "--format", "ndjson", out_path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success(), "show ndjson should succeed");
+
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    let lines: Vec<Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each line should be a JSON object"))
+        .collect();
+    assert_eq!(lines.len(), 2, "unexpected output: {stdout}");
+    assert_eq!(lines[0]["record"], 1);
+    assert_eq!(lines[0]["path"], out_path);
+    assert_eq!(lines[0]["stdout"], "first-ndjson");
+    assert_eq!(lines[1]["record"], 2);
+    assert_eq!(lines[1]["stdout"], "second-ndjson");
+}
+
+#[test]
+fn show_text_lists_meta_in_file_order() {
+    let dir = "target/tmp/show_meta_order";
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/order.rommy");
+    fs::write(
+        &path,
+        "<<<META>>>\nzeta: 1\nformat_version: 2\nalpha: 2\n<<<END>>>\n\
+<<<COMMAND>>>\n$ true\n<<<END>>>\n<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n",
+    )
+    .expect("failed to write record");
+
+    let show = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["show", &path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success());
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    assert!(
+        stdout.contains("<<<META>>>\nzeta: 1\nformat_version: 2\nalpha: 2\n<<<END>>>"),
+        "unexpected output: {stdout}"
+    );
+}
+
+#[test]
+fn show_ndjson_prints_records_before_reading_the_rest() {
+    let dir = "target/tmp/show_ndjson_stream";
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/cut.rommy");
+    let record = "<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n$ true\n<<<END>>>\n\
+<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n";
+    // The second record is cut off; the first is printed before that is noticed
+    fs::write(&path, format!("{record}<<<META>>>\nstatus: ok\n")).expect("failed to write");
+
+    let show = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["show", "--format", "ndjson", &path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(
+        !show.status.success(),
+        "the broken record is still an error"
+    );
+    let stdout = String::from_utf8_lossy(&show.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1, "{stdout}");
+    let first: Value = serde_json::from_str(lines[0]).expect("NDJSON line");
+    assert_eq!(first["record"], 1);
+}
+
+#[test]
+fn show_json_keeps_meta_order_and_duplicates() {
+    let dir = "target/tmp/show_meta_entries";
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/dup.rommy");
+    fs::write(
+        &path,
+        "<<<META>>>\nzeta: 1\nalpha: 2\nzeta: 3\n<<<END>>>\n\
+<<<COMMAND>>>\n$ true\n<<<END>>>\n<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n",
+    )
+    .expect("failed to write record");
+
+    let show = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["show", "--format", "json", &path])
+        .output()
+        .expect("failed to execute rommy show");
+    assert!(show.status.success());
+    let value: Value = serde_json::from_slice(&show.stdout).expect("show should emit JSON");
+    let record = &value["records"][0];
+    assert_eq!(
+        record["meta_entries"],
+        serde_json::json!([["zeta", "1"], ["alpha", "2"], ["zeta", "3"]])
+    );
+    assert_eq!(record["meta"]["zeta"], "3");
+    assert!(
+        record["meta"].get("format_version").is_none(),
+        "only keys from the file are shown"
+    );
+}
@@ -128,3 +128,153 @@ fn validate_json_outputs_machine_readable_result() {
     assert!(parsed["files"].is_array());
     assert_eq!(parsed["files"].as_array().map(|a| a.len()), Some(2));
 }
+
+#[test]
+fn validate_ndjson_writes_one_object_per_file() {
+    let dir = "target/tmp/validate_ndjson";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    fs::write(
+        format!("{dir}/a_ok.rommy"),
+        "<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n$ echo x\n<<<END>>>\n<<<STDOUT>>>\nx\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n",
+    )
+    .expect("failed to write valid rommy file");
+    fs::write(
+        format!("{dir}/b_bad.rommy"),
+        "<<<META>>>\nstatus: ok\n<<<END>>>\n",
+    )
+    .expect("failed to write invalid rommy file");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let validate = Command::new(bin)
+        .args(["validate", "--format", "ndjson", dir])
+        .output()
+        .expect("failed to execute rommy validate");
+    assert!(!validate.status.success(), "validate should fail");
+
+    let stdout = String::from_utf8_lossy(&validate.stdout);
+    let lines: Vec<Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("each line should be a JSON object"))
+        .collect();
+    assert_eq!(lines.len(), 2, "unexpected output: {stdout}");
+    assert_eq!(lines[0]["valid"], true);
+    assert_eq!(lines[0]["records"], 1);
+    assert_eq!(lines[1]["valid"], false);
+    assert!(lines[1]["error"].is_string());
+}
+
+#[test]
+fn validate_reports_line_of_parse_error() {
+    let dir = "target/tmp/validate_position";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let path = format!("{dir}/marker.rommy");
+    // Second record: STDOUT is never closed before STDERR starts (line 16)
+    fs::write(
+        &path,
+        "<<<META>>>\nstatus: ok\n<<<END>>>\n<<<COMMAND>>>\n$ echo x\n<<<END>>>\n<<<STDOUT>>>\nx\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n\
+         <<<META>>>\nstatus: ok\n<<<END>>>\n<<<STDOUT>>>\n<<<STDERR>>>\n<<<END>>>\n",
+    )
+    .expect("failed to write invalid rommy file");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let text = Command::new(bin)
+        .args(["validate", &path])
+        .output()
+        .expect("failed to execute rommy validate");
+    assert!(!text.status.success());
+    let stderr = String::from_utf8_lossy(&text.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "ERR {path}:16: unexpected start of block STDERR before closing STDOUT block"
+        )),
+        "unexpected output: {stderr}"
+    );
+
+    let json = Command::new(bin)
+        .args(["validate", "--format", "ndjson", &path])
+        .output()
+        .expect("failed to execute rommy validate");
+    let entry: Value = serde_json::from_slice(&json.stdout).expect("ndjson line");
+    assert_eq!(entry["error_kind"], "unexpected_marker");
+    assert_eq!(entry["line"], 16);
+    assert_eq!(entry["record"], 2);
+    assert_eq!(entry["offset"], 158);
+}
+
+#[test]
+fn validate_strict_reports_semantic_issues() {
+    let dir = "target/tmp/validate_strict";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).expect("failed to create test dir");
+    let record = |start: &str, extra: &str| {
+        format!(
+            "<<<META>>>\nrommy_version: 0.1.0\ncwd: /\ncommand_line: make\n\
+             start_ts: {start}T00:00:00Z\nend_ts: {start}T00:00:01Z\nduration_ms: 1000\n\
+             {extra}<<<END>>>\n<<<COMMAND>>>\n$ make\n<<<END>>>\n<<<STDOUT>>>\n<<<END>>>\n<<<STDERR>>>\n<<<END>>>\n"
+        )
+    };
+    let path = format!("{dir}/issues.rommy");
+    fs::write(
+        &path,
+        record(
+            "2025-01-02",
+            "status: ok\nexit_code: 0\nlabel: a\nlabel: b\n",
+        ) + &record("2025-01-01", "status: ok\nexit_code: 2\n"),
+    )
+    .expect("failed to write rommy file");
+
+    let bin = env!("CARGO_BIN_EXE_rommy");
+    let plain = Command::new(bin)
+        .args(["validate", &path])
+        .status()
+        .expect("failed to execute rommy validate");
+    assert!(plain.success(), "structure alone is fine");
+
+    let strict = Command::new(bin)
+        .args(["validate", "--strict", "--format", "ndjson", &path])
+        .output()
+        .expect("failed to execute rommy validate");
+    assert!(!strict.status.success(), "status-mismatch is an error");
+    let entry: Value = serde_json::from_slice(&strict.stdout).expect("ndjson line");
+    assert_eq!(entry["valid"], false);
+    let issues: Vec<(String, String, u64)> = entry["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| {
+            (
+                i["code"].as_str().unwrap().to_string(),
+                i["level"].as_str().unwrap().to_string(),
+                i["record"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        issues,
+        vec![
+            ("duplicate-key".to_string(), "warning".to_string(), 1),
+            ("out-of-order".to_string(), "warning".to_string(), 2),
+            ("status-mismatch".to_string(), "error".to_string(), 2),
+        ]
+    );
+
+    // Warnings alone keep the file valid
+    let warn_path = format!("{dir}/warnings.rommy");
+    fs::write(
+        &warn_path,
+        record("2025-01-01", "duration_ms: 5\nstatus: ok\nexit_code: 0\n"),
+    )
+    .expect("failed to write rommy file");
+    let warned = Command::new(bin)
+        .args(["validate", "--strict", &warn_path])
+        .output()
+        .expect("failed to execute rommy validate");
+    assert!(warned.status.success(), "warnings do not fail validation");
+    let stderr = String::from_utf8_lossy(&warned.stderr);
+    assert!(
+        stderr.contains(&format!("WARN {warn_path}#1: ")) && stderr.contains("[duplicate-key]"),
+        "unexpected output: {stderr}"
+    );
+}
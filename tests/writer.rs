@@ -0,0 +1,132 @@
+use chrono::{TimeZone, Utc};
+use rommy::parser::{parse_file, parse_str};
+use rommy::writer::{
+    RecordBuilder, RecordLock, RommyCommand, append_record, lock_path, write_record,
+};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn sample(word: &str) -> rommy::parser::RommyRecord {
+    let start = Utc.with_ymd_and_hms(2025, 10, 26, 16, 59, 0).unwrap();
+    let end = start + chrono::Duration::milliseconds(1250);
+    RecordBuilder::new(&RommyCommand::Line(format!("echo {word}")))
+        .label("harness")
+        .cwd(Path::new("/tmp"))
+        .times(start, end)
+        .meta("suite", "writer")
+        .exit(1, "error")
+        .stdout(format!("{word}\n\nblank line above\n"))
+        .stderr("oops")
+        .stdout_ansi(format!("\x1b[1m{word}\x1b[0m\n"))
+        .build()
+}
+
+#[test]
+fn written_record_parses_back_unchanged() {
+    let record = sample("hello");
+    let mut bytes = Vec::new();
+    write_record(&mut bytes, &record).expect("write failed");
+    let text = String::from_utf8(bytes).expect("record is UTF-8");
+
+    let parsed = parse_str(&text).expect("written record should parse");
+    assert_eq!(parsed, vec![record]);
+    assert_eq!(parsed[0].meta["duration_ms"], "1250");
+    assert!(text.starts_with("<<<META>>>\nrommy_version: "), "{text}");
+    assert!(
+        text.contains("suite: writer\nstatus: error\nexit_code: 1\n<<<END>>>"),
+        "{text}"
+    );
+}
+
+#[test]
+fn leading_blank_lines_round_trip() {
+    let mut record = sample("blank");
+    record.stdout = "\n\nafter two blank lines".to_string();
+    record.stderr = "\n".to_string();
+    record.timing = Some(String::new());
+    let mut bytes = Vec::new();
+    write_record(&mut bytes, &record).expect("write failed");
+    let text = String::from_utf8(bytes).expect("record is UTF-8");
+
+    let parsed = parse_str(&text).expect("written record should parse");
+    assert_eq!(parsed, vec![record]);
+}
+
+#[test]
+fn append_record_adds_to_plain_and_compressed_files() {
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    for path in ["target/tmp/writer.rommy", "target/tmp/writer.rommy.zst"] {
+        let _ = fs::remove_file(path);
+        append_record(path, &sample("first")).expect("first append failed");
+        append_record(path, &sample("second")).expect("second append failed");
+
+        let records = parse_file(path).expect("appended file should parse");
+        assert_eq!(records, vec![sample("first"), sample("second")], "{path}");
+    }
+
+    // The CLI reads and appends to the same file
+    let path = "target/tmp/writer.rommy";
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args([
+            "run",
+            "--no-stream",
+            "--append",
+            "--out",
+            path,
+            "--",
+            "echo",
+            "third",
+        ])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success());
+    let validate = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["validate", path])
+        .status()
+        .expect("failed to execute rommy validate");
+    assert!(validate.success(), "mixed file should validate");
+    assert_eq!(parse_file(path).unwrap().len(), 3);
+}
+
+#[test]
+fn invalid_utf8_output_is_stored_with_replacement_characters() {
+    let out = "target/tmp/writer_invalid_utf8.rommy";
+    let _ = fs::remove_file(out);
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rommy"))
+        .args(["run", "--no-stream", "--out", out, "--"])
+        .args(["printf", "ok \\377\\n"])
+        .status()
+        .expect("failed to execute rommy run");
+    assert!(status.success(), "rommy run should succeed");
+    let recs = parse_file(out).expect("record should stay readable");
+    assert_eq!(recs[0].stdout, "ok \u{FFFD}");
+}
+
+#[test]
+fn waiting_writer_locks_the_current_file_after_removal() {
+    use fs2::FileExt;
+
+    fs::create_dir_all("target/tmp").expect("failed to create target/tmp");
+    let path = Path::new("target/tmp/writer_lock_removed.rommy");
+    let held = RecordLock::acquire(path).expect("first lock");
+
+    let waiter = std::thread::spawn(move || {
+        let lock = RecordLock::acquire(path).expect("second lock");
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        drop(lock);
+    });
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    held.remove();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    // The waiter must hold the lock file that exists now, not the removed one
+    let current = fs::File::open(lock_path(path)).expect("lock file should exist again");
+    assert!(
+        current.try_lock_exclusive().is_err(),
+        "the current lock file should be held by the waiter"
+    );
+    waiter.join().expect("waiter panicked");
+}